@@ -1,5 +1,5 @@
 use super::*;
-use blockchain_types::{BlockContents, Transaction};
+use blockchain_types::{short_hash, BlockContents, Transaction};
 use common::PseudorandomColors;
 use nakamoto_consensus::NakamotoNodeState;
 
@@ -237,6 +237,7 @@ impl BlockchainView {
 
         html! {
             <g>
+                <title>{ format!("Block {}", short_hash(block_id)) }</title>
                 <rect
                     x={ block_x.to_string() }
                     y="0"
@@ -391,10 +392,10 @@ impl BlockchainView {
 
 impl Cache {
     fn update(&mut self, state: &NakamotoNodeState) -> bool {
-        if state.tip() != self.blockchain_tip
+        if Some(state.tip()) != self.blockchain_tip
             || self.n_tx_unconfirmed != state.txes_unconfirmed().len()
         {
-            self.blockchain_tip = state.tip();
+            self.blockchain_tip = Some(state.tip());
             self.n_tx_unconfirmed = state.txes_unconfirmed().len();
             true
         } else {
@@ -410,14 +411,15 @@ fn last_blocks_in_longest_chain(
     sim: &Simulation,
 ) -> Vec<(Option<Entity>, Vec<(Option<Entity>, String)>)> {
     let mut chain = vec![];
-    let mut block_id = state.tip();
+    let mut block_id = Some(state.tip());
     for _ in 0..max_blocks {
         // The block with id `None` will be counted as the genesis block
-        chain.push((block_id, get_transactions_shortform(block_id, sim)));
-        if block_id == None {
+        let id = block_id.filter(|&id| id != state.genesis_block());
+        chain.push((id, get_transactions_shortform(id, sim)));
+        if id.is_none() {
             break;
         }
-        block_id = state.block_header(block_id.unwrap()).unwrap().id_prev;
+        block_id = state.previous_block(id.unwrap());
     }
     chain
 }
@@ -16,7 +16,7 @@ mod hash_box;
 pub use hash_box::HashBox;
 
 mod net_view;
-pub use net_view::NetView;
+pub use net_view::{MessageCollector, NetView};
 
 mod spinner;
 pub use spinner::Spinner;
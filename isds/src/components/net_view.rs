@@ -1,14 +1,18 @@
 use super::*;
 use common::PseudorandomColors;
 
-use std::collections::btree_map::Entry;
-use std::collections::BTreeMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
 
 pub struct NetView {
     sim: SharedSimulation,
     highlight: Highlight,
     colors: PseudorandomColors,
     edges: EdgeMap,
+    block_cutouts: BlockCutoutCache,
+    reorg_flashes: ReorgFlashes,
+    selected: Option<Entity>,
     _context_handle: yew::context::ContextHandle<IsdsContext>,
 }
 
@@ -19,6 +23,7 @@ pub enum Msg {
     NodeMouseOver(Entity),
     NodeMouseOut,
     LinkClick(Entity, Entity),
+    SelectNode(Option<Entity>),
 }
 
 #[derive(Properties, PartialEq)]
@@ -29,17 +34,110 @@ pub struct Props {
     #[prop_or(false)]
     pub node_highlight_on_hover: bool,
 
+    /// Mine a block at a clicked node. Only used if no `on_node_click` callback is set and
+    /// clicks don't toggle the highlight (see `node_highlight_on_hover`).
+    #[prop_or(true)]
+    pub mine_on_click: bool,
+
     #[prop_or(true)]
     pub toggle_edges_on_click: bool,
 
+    /// Keep a clicked node (and its edges) emphasized until it is clicked again. Independent of
+    /// `on_node_click`.
+    #[prop_or(false)]
+    pub select_on_click: bool,
+
     #[prop_or_default()]
     pub highlight_class: Classes,
 
     #[prop_or(50.)]
     pub buffer_space: f32,
+
+    /// Radius of the circles representing nodes.
+    #[prop_or(5.)]
+    pub node_radius: f32,
+
+    /// Radius of the circles representing regular messages (such as transactions).
+    #[prop_or(1.5)]
+    pub message_radius: f32,
+
+    /// Color of regular messages and their trails. Prominent messages (such as blocks) are
+    /// colored like the payload they carry.
+    #[prop_or_else(|| "black".to_string())]
+    pub message_color: String,
+
+    /// Radius of the circles representing prominent messages (such as blocks).
+    #[prop_or(2.)]
+    pub prominent_message_radius: f32,
+
+    /// How far back (in real seconds of flight) the fading trail behind each in-flight message
+    /// reaches. Set to `0.` to disable trails. The default draws short trails at default speeds.
+    #[prop_or(0.025)]
+    pub trail_seconds: RealSeconds,
+
+    /// Scale node radii by the nodes' `MiningPower` (within bounds, so that all nodes remain
+    /// legible).
+    #[prop_or(false)]
+    pub scale_nodes_by_mining_power: bool,
+
+    /// How many blocks (counted back from the tip) are shown next to each node.
+    #[prop_or(5)]
+    pub block_view_depth: usize,
+
+    /// Annotate the tip of each node's block cutout with its height.
+    #[prop_or(false)]
+    pub show_block_heights: bool,
+
+    /// Show the number of unconfirmed transactions in each node's mempool next to the node.
+    #[prop_or(false)]
+    pub show_mempool_sizes: bool,
+
+    /// Show a legend explaining the edge styles (and, if `toggle_edges_on_click` is set, how to
+    /// toggle edges) in the top left corner.
+    #[prop_or(false)]
+    pub show_legend: bool,
+
+    /// Color edges on a gradient from green to red by their latency (see
+    /// `Simulation::link_latency`), relative to the slowest edge.
+    #[prop_or(false)]
+    pub color_edges_by_latency: bool,
+
+    /// Show the sim time and a tip height in the top right corner: the selected node's, or the
+    /// highest one if no node is selected.
+    #[prop_or(false)]
+    pub show_hud: bool,
+
+    /// Briefly flash a ring around nodes that switch to another fork (i.e., whose new tip doesn't
+    /// extend their previous one). The ring is red if the switch reverted confirmed transactions.
+    #[prop_or(false)]
+    pub flash_on_reorg: bool,
+
+    /// Index of a `LinkUsage` event handler. If set, edges are drawn thicker the more messages
+    /// were recently sent along them.
+    #[prop_or_default]
+    pub link_usage_handler_index: Option<usize>,
+
+    /// Collects the messages to draw. Defaults to the inventory messages of Nakamoto consensus.
+    #[prop_or_default]
+    pub message_collector: MessageCollector,
     // TODO a lot more things should be props really
 }
 
+/// Wraps a function collecting the messages that `NetView` draws, e.g.,
+/// `simple_flooding::inventory_messages::<T>` for protocols flooding `Inventory` items of type `T`.
+#[derive(Clone, Copy)]
+pub struct MessageCollector(pub fn(&World, SimSeconds) -> Vec<simple_flooding::InventoryMessage>);
+impl Default for MessageCollector {
+    fn default() -> Self {
+        Self(simple_flooding::inventory_messages::<nakamoto_consensus::InventoryItem>)
+    }
+}
+impl PartialEq for MessageCollector {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 as usize == other.0 as usize
+    }
+}
+
 impl Component for NetView {
     type Message = Msg;
     type Properties = Props;
@@ -61,12 +159,16 @@ impl Component for NetView {
             highlight,
             colors,
             edges,
+            block_cutouts: Default::default(),
+            reorg_flashes: Default::default(),
+            selected: None,
             _context_handle,
         }
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let buffer_space = ctx.props().buffer_space;
+        let sim = self.sim.borrow();
         html! {
             <>
                 <style>
@@ -77,14 +179,33 @@ impl Component for NetView {
                     viewBox={ format!("{} {} {} {}",
                        -buffer_space,
                        -buffer_space,
-                       self.sim.borrow().underlay_width() + 2. * buffer_space,
-                       self.sim.borrow().underlay_height() + 2. * buffer_space
+                       sim.underlay_width() + 2. * buffer_space,
+                       sim.underlay_height() + 2. * buffer_space
                     ) }
                 >
                     // { self.view_palette() }
-                    { self.view_edges(ctx) }
-                    { self.view_nodes(ctx) }
-                    { self.view_messages(ctx) }
+                    { self.view_edges(ctx, &sim) }
+                    { self.view_nodes(ctx, &sim) }
+                    { self.view_messages(ctx, &sim) }
+                    if ctx.props().show_legend {
+                        {
+                            view_legend(
+                                -buffer_space + 10.,
+                                -buffer_space + 10.,
+                                ctx.props().toggle_edges_on_click,
+                            )
+                        }
+                    }
+                    if ctx.props().show_hud {
+                        {
+                            view_hud(
+                                sim.underlay_width() + buffer_space - 10.,
+                                -buffer_space + 10.,
+                                sim.time.now(),
+                                self.hud_tip_height(&sim),
+                            )
+                        }
+                    }
                 </svg>
             </>
         }
@@ -92,16 +213,27 @@ impl Component for NetView {
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
-            Msg::Rendered(_) => {
+            Msg::Rendered(time) => {
                 self.rebuild_edges_if_changed();
+                if ctx.props().flash_on_reorg {
+                    self.reorg_flashes.update(&self.sim.borrow().world, time);
+                }
                 true // often enough, we'll have in-flight messages that have to be redrawn
             }
             Msg::NodeClick(node) => {
                 log!(format!("Click on {}", self.sim.borrow().name(node)));
+                if ctx.props().select_on_click {
+                    let selected = (self.selected != Some(node)).then_some(node);
+                    ctx.link().send_message(Msg::SelectNode(selected));
+                }
                 if let Some(on_node_click) = ctx.props().on_node_click.as_ref() {
                     on_node_click.emit(node);
                 } else if ctx.props().node_highlight_on_hover {
                     self.highlight.toggle_select(node);
+                } else if ctx.props().mine_on_click {
+                    self.sim
+                        .borrow_mut()
+                        .do_now(nakamoto_consensus::MineBlockAtNode(node));
                 }
                 false
             }
@@ -130,33 +262,61 @@ impl Component for NetView {
                         self.sim.borrow().name(node2)
                     ));
                     if self.edges.edge_type(node1, node2).unwrap().is_phantom() {
-                        self.sim.borrow_mut().do_now(AddPeer(node1, node2));
-                        self.sim.borrow_mut().do_now(AddPeer(node2, node1));
+                        self.sim.borrow_mut().do_now(AddLink(node1, node2));
                     } else {
-                        self.sim.borrow_mut().do_now(RemovePeer(node1, node2));
-                        self.sim.borrow_mut().do_now(RemovePeer(node2, node1));
+                        self.sim.borrow_mut().do_now(RemoveLink(node1, node2));
                     }
                 }
                 false
             }
+            Msg::SelectNode(node) => {
+                self.selected = node;
+                true
+            }
         }
     }
 }
 
 impl NetView {
     fn rebuild_edges_if_changed(&mut self) -> bool {
-        let now = self.sim.borrow().time.now();
-        self.edges.rebuild_if_needed(&self.sim.borrow().world, now)
+        let sim = self.sim.borrow();
+        self.edges.rebuild_if_needed(&sim.world, sim.time.now())
     }
-    fn view_nodes(&self, ctx: &Context<NetView>) -> Html {
-        let r = 5.0;
+    fn hud_tip_height(&self, sim: &Simulation) -> usize {
+        let tip_height = |node| {
+            sim.world
+                .get::<nakamoto_consensus::NakamotoNodeState>(node)
+                .map_or(0, |state| state.tip_height())
+        };
+        match self.selected {
+            Some(node) => tip_height(node),
+            None => sim
+                .world
+                .query::<&nakamoto_consensus::NakamotoNodeState>()
+                .iter()
+                .map(|(_, state)| state.tip_height())
+                .max()
+                .unwrap_or(0),
+        }
+    }
+    fn view_nodes(&self, ctx: &Context<NetView>, sim: &Simulation) -> Html {
         let link = ctx.link();
-        self.sim
-            .borrow()
+        let nodes: Html = sim
             .world
-            .query::<(&UnderlayPosition, &nakamoto_consensus::NakamotoNodeState)>()
+            .query::<(
+                &UnderlayPosition,
+                &nakamoto_consensus::NakamotoNodeState,
+                Option<&nakamoto_consensus::MiningPower>,
+                Option<&NodeColor>,
+            )>()
             .into_iter()
-            .map(|(node, (pos, node_state))| {
+            .map(|(node, (pos, node_state, mining_power, color))| {
+                let is_selected = self.selected == Some(node);
+                let r = if ctx.props().scale_nodes_by_mining_power {
+                    scaled_node_radius(ctx.props().node_radius, mining_power.copied())
+                } else {
+                    ctx.props().node_radius
+                };
                 html! {
                     <g>
                         <circle
@@ -167,29 +327,107 @@ impl NetView {
                                         .then_some(ctx.props().highlight_class.clone()),
                                         (
                                             ctx.props().on_node_click.is_some() ||
-                                            ctx.props().node_highlight_on_hover
+                                            ctx.props().mine_on_click ||
+                                            ctx.props().node_highlight_on_hover ||
+                                            ctx.props().select_on_click
                                         ).then_some("is-clickable"),
                                 )
                             }
                             cx={ pos.x.to_string() }
                             cy={ pos.y.to_string() }
                             r={ r.to_string() }
+                            fill={ node_fill(color) }
+                            stroke={ if is_selected { "black" } else { "none" } }
+                            stroke-width="2"
                             onclick={ link.callback(move |_| Msg::NodeClick(node)) }
                             onmouseover={ link.callback(move |_| Msg::NodeMouseOver(node)) }
                             onmouseout={ link.callback(|_| Msg::NodeMouseOut) }
                         />
-                        { self.view_blocks(node_state, pos.x + 8., pos.y - 8.) }
+                        if self.reorg_flashes.intensity(node) > 0. {
+                            <circle
+                                cx={ pos.x.to_string() }
+                                cy={ pos.y.to_string() }
+                                r={ (r + 4.).to_string() }
+                                fill="none"
+                                stroke={
+                                    if self.reorg_flashes.reverted_confirmed_txes(node) {
+                                        "red"
+                                    } else {
+                                        "orange"
+                                    }
+                                }
+                                stroke-width="3"
+                                opacity={ self.reorg_flashes.intensity(node).to_string() }
+                                pointer-events="none"
+                            />
+                        }
+                        {
+                            self.view_blocks(
+                                node,
+                                node_state,
+                                pos.x + 8.,
+                                pos.y - 8.,
+                                ctx.props().block_view_depth,
+                                ctx.props().show_block_heights,
+                            )
+                        }
+                        if ctx.props().show_mempool_sizes {
+                            {
+                                view_mempool_badge(
+                                    pos.x - r - 4.,
+                                    pos.y + r + 4.,
+                                    node_state.txes_unconfirmed().len(),
+                                )
+                            }
+                        }
                     </g>
                 }
             })
-            .collect()
+            .collect();
+        self.block_cutouts.evict_unseen();
+        nodes
     }
-    fn view_edges(&self, ctx: &Context<NetView>) -> Html {
+    fn view_edges(&self, ctx: &Context<NetView>, sim: &Simulation) -> Html {
         let link = ctx.link();
+        let now = sim.time.now();
+        let handlers = sim.additional_event_handlers();
+        let handlers = handlers.borrow();
+        let link_usage = ctx
+            .props()
+            .link_usage_handler_index
+            .and_then(|i| handlers.get::<LinkUsage>(i));
+        let max_usage = link_usage.map_or(0, |link_usage| link_usage.max_usage(now));
+        let max_latency = self
+            .edges
+            .edges
+            .values()
+            .filter(|(edge_type, _)| !edge_type.is_phantom())
+            .map(|(_, line)| sim.link_latency(line))
+            .max()
+            .unwrap_or_default();
         self.edges
             .edges
             .iter()
             .map(|(&edge_endpoints, &(edge_type, line))| {
+                let (undirected_color, directed_color) = if ctx.props().color_edges_by_latency {
+                    let color = latency_color(sim.link_latency(&line), max_latency);
+                    (color.clone(), color)
+                } else {
+                    ("gray".to_string(), "lightgray".to_string())
+                };
+                let mut stroke_width = if self.selected.is_some_and(|node| {
+                    node == edge_endpoints.left() || node == edge_endpoints.right()
+                }) {
+                    3.
+                } else {
+                    1.
+                };
+                if let Some(link_usage) = link_usage.filter(|_| max_usage > 0) {
+                    let usage =
+                        link_usage.usage(edge_endpoints.left(), edge_endpoints.right(), now);
+                    stroke_width += MAX_EXTRA_EDGE_WIDTH * usage as f32 / max_usage as f32;
+                }
+                let stroke_width = stroke_width.to_string();
                 html! {
                     <g
                         onclick={ link.callback(move |_| Msg::LinkClick(
@@ -216,7 +454,8 @@ impl NetView {
                                     y1={ line.start.y.to_string() }
                                     x2={ line.end.x.to_string() }
                                     y2={ line.end.y.to_string() }
-                                    stroke="gray"
+                                    stroke={ undirected_color }
+                                    stroke-width={ stroke_width }
                                     class={
                                         classes!(
                                             ctx.props().toggle_edges_on_click.then_some("is-clickable")
@@ -230,7 +469,8 @@ impl NetView {
                                     y1={ line.start.y.to_string() }
                                     x2={ line.end.x.to_string() }
                                     y2={ line.end.y.to_string() }
-                                    stroke="lightgray"
+                                    stroke={ directed_color }
+                                    stroke-width={ stroke_width }
                                     stroke-dasharray="8,8"
                                     class={
                                         classes!(
@@ -245,60 +485,103 @@ impl NetView {
             })
             .collect()
     }
-    fn view_messages(&self, ctx: &Context<NetView>) -> Html {
-        // TODO: currently more like: view_nakamoto_consensus_messages...
-        let time_now = self.sim.borrow().time.now();
-        self.sim
-            .borrow()
-            .world
-            .query::<(
-                &UnderlayLine,
-                &TimeSpan,
-                &simple_flooding::SimpleFloodingMessage<nakamoto_consensus::InventoryItem>,
-            )>()
+    fn view_messages(&self, ctx: &Context<NetView>, sim: &Simulation) -> Html {
+        let time_now = sim.time.now();
+        let messages = (ctx.props().message_collector.0)(&sim.world, time_now);
+        let mut messages_per_trajectory: BTreeMap<[u32; 4], usize> = BTreeMap::new();
+        for message in messages.iter() {
+            *messages_per_trajectory
+                .entry(trajectory_key(&message.trajectory))
+                .or_default() += 1;
+        }
+        messages
             .into_iter()
-            .map(|(_, (trajectory, time_span, message))| {
-                let (x, y) = message_position(trajectory, time_span, time_now);
-                match message.0 {
-                    nakamoto_consensus::InventoryItem::Transaction(txid) => {
-                        html! {
+            .map(|message| {
+                let is_crowded = messages_per_trajectory[&trajectory_key(&message.trajectory)] > 1;
+                let trajectory = &if is_crowded {
+                    jittered_trajectory(&message.trajectory, message.message)
+                } else {
+                    message.trajectory
+                };
+                let (x, y, progress) = message_position(trajectory, &message.time_span, time_now);
+                let trail_progress = trail_progress(
+                    ctx.props().trail_seconds,
+                    sim.time.speed(),
+                    &message.time_span,
+                );
+                if message.is_prominent {
+                    let color = self.colors.get(message.entity.id());
+                    html! {
+                        <g>
+                            { view_message_trail(trajectory, progress, trail_progress, color) }
+                            <circle
+                                cx={ x.to_string() }
+                                cy={ y.to_string() }
+                                r={ ctx.props().prominent_message_radius.to_string() }
+                                fill={ color.to_string() }
+                            />
+                        </g>
+                    }
+                } else {
+                    html! {
+                        <g>
+                            {
+                                view_message_trail(
+                                    trajectory,
+                                    progress,
+                                    trail_progress,
+                                    &ctx.props().message_color,
+                                )
+                            }
                             <circle
                                 class={
                                     classes!(
                                         self.highlight
-                                            .is(txid)
+                                            .is(message.entity)
                                             .then_some(ctx.props().highlight_class.clone()),
                                     )
                                 }
                                 cx={ x.to_string() }
                                 cy={ y.to_string() }
-                                r=1.5
+                                r={ ctx.props().message_radius.to_string() }
+                                fill={ ctx.props().message_color.clone() }
                             />
-                        }
-                    }
-                    nakamoto_consensus::InventoryItem::Block(block_id) => {
-                        html! {
-                            <circle
-                                cx={ x.to_string() }
-                                cy={ y.to_string() }
-                                r=2
-                                fill={ self.colors.get(block_id.id()).to_string() }
-                            />
-                        }
+                        </g>
                     }
                 }
             })
             .collect()
     }
-    fn view_blocks(&self, state: &nakamoto_consensus::NakamotoNodeState, x: f32, y: f32) -> Html {
-        let max_depth = 5;
+    fn view_blocks(
+        &self,
+        node: Entity,
+        state: &nakamoto_consensus::NakamotoNodeState,
+        x: f32,
+        y: f32,
+        max_depth: usize,
+        show_height: bool,
+    ) -> Html {
         let block_height = 5.;
         let block_width = 5.;
         let block_spacing = 2.;
+        let font_size = 5.;
 
-        let block_map = blocks_cutout(state, max_depth);
+        let block_map = self.block_cutouts.get(node, state, max_depth);
         let mut result = vec![];
 
+        if show_height && !state.is_at_genesis() {
+            result.push(html! {
+                <text
+                    x={ x.to_string() }
+                    y={ (y - block_spacing).to_string() }
+                    font-size={ font_size.to_string() }
+                    font-family="monospace"
+                >
+                    { state.tip_height() }
+                </text>
+            });
+        }
+
         for i in 0..block_map.len() {
             for j in 0..block_map[i].len() {
                 if let Some(block_id) = block_map[i][j] {
@@ -370,21 +653,247 @@ impl NetView {
     }
 }
 
+/// A small badge with the number of unconfirmed transactions, centered at the given position.
+/// Empty mempools aren't shown.
+fn view_mempool_badge(x: f32, y: f32, mempool_size: usize) -> Html {
+    if mempool_size == 0 {
+        return html! {};
+    }
+    html! {
+        <g>
+            <circle
+                cx={ x.to_string() }
+                cy={ y.to_string() }
+                r=4
+                fill="black"
+            />
+            <text
+                x={ x.to_string() }
+                y={ y.to_string() }
+                font-size="5"
+                font-family="monospace"
+                fill="white"
+                text-anchor="middle"
+                dominant-baseline="central"
+            >
+                { mempool_size }
+            </text>
+        </g>
+    }
+}
+
+/// Green for the fastest, red for the slowest links.
+fn latency_color(latency: SimSeconds, max_latency: SimSeconds) -> String {
+    let slowness = if max_latency > OrderedFloat(0.) {
+        (latency / max_latency).into_inner().clamp(0., 1.)
+    } else {
+        0.
+    };
+    format!("hsl({:.0}, 80%, 40%)", 120. * (1. - slowness))
+}
+
+/// Right-aligned at `x`.
+fn view_hud(x: f32, y: f32, time_now: SimSeconds, tip_height: usize) -> Html {
+    html! {
+        <text
+            class="is-unselectable"
+            x={ x.to_string() }
+            y={ y.to_string() }
+            font-size="12"
+            font-family="monospace"
+            text-anchor="end"
+            dominant-baseline="hanging"
+        >
+            { format!("t = {:.1} s, height {}", time_now.into_inner(), tip_height) }
+        </text>
+    }
+}
+
+/// Sample glyphs for the edge types, styled like in `view_edges`.
+fn view_legend(x: f32, y: f32, explain_phantom_links: bool) -> Html {
+    let line_length = 30.;
+    let row_height = 16.;
+    let view_row = |row: usize, line: Html, text: &str| {
+        let row_y = y + row as f32 * row_height;
+        html! {
+            <g transform={ format!("translate({} {})", x, row_y) }>
+                { line }
+                <text x={ (line_length + 8.).to_string() } y="4" font-size="12">{ text }</text>
+            </g>
+        }
+    };
+    html! {
+        <g class="is-unselectable">
+            {
+                view_row(
+                    0,
+                    html! {
+                        <line x1="0" y1="0" x2={ line_length.to_string() } y2="0" stroke="gray" />
+                    },
+                    "peers of each other",
+                )
+            }
+            {
+                view_row(
+                    1,
+                    html! {
+                        <line
+                            x1="0"
+                            y1="0"
+                            x2={ line_length.to_string() }
+                            y2="0"
+                            stroke="lightgray"
+                            stroke-dasharray="8,8"
+                        />
+                    },
+                    "peer in one direction only",
+                )
+            }
+            if explain_phantom_links {
+                {
+                    view_row(
+                        2,
+                        html! {
+                            <line
+                                x1="0"
+                                y1="0"
+                                x2={ line_length.to_string() }
+                                y2="0"
+                                stroke="gray"
+                                stroke-opacity="0.3"
+                                stroke-width=8
+                            />
+                        },
+                        "hover to reveal possible links, click to add or remove",
+                    )
+                }
+            }
+        </g>
+    }
+}
+
+/// Scales `base_radius` with the square root of the mining power (so that the area is
+/// proportional to it), clamped to between half and three times the base radius.
+fn scaled_node_radius(
+    base_radius: f32,
+    mining_power: Option<nakamoto_consensus::MiningPower>,
+) -> f32 {
+    let mining_power = mining_power.unwrap_or_default().0.max(0.);
+    base_radius * (mining_power.sqrt() as f32).clamp(0.5, 3.)
+}
+
+/// The custom color of the node if it has one, otherwise the SVG default.
+fn node_fill(color: Option<&NodeColor>) -> String {
+    color.map_or_else(|| "black".to_string(), |color| color.0.clone())
+}
+
+/// Returns the current position of a message together with its progress (between 0 and 1).
 fn message_position(
     trajectory: &UnderlayLine,
     time_span: &TimeSpan,
     time_now: SimSeconds,
-) -> (f32, f32) {
+) -> (f32, f32, f32) {
     let progress = time_span.progress_clamped(time_now) as f32;
-    // clippy said that `mul_add` could be faster...
-    let x = (trajectory.end.x - trajectory.start.x).mul_add(progress, trajectory.start.x);
-    let y = (trajectory.end.y - trajectory.start.y).mul_add(progress, trajectory.start.y);
-    (x, y)
+    let (x, y) = point_on_line(trajectory, progress);
+    (x, y, progress)
+}
+
+fn trajectory_key(trajectory: &UnderlayLine) -> [u32; 4] {
+    [
+        trajectory.start.x.to_bits(),
+        trajectory.start.y.to_bits(),
+        trajectory.end.x.to_bits(),
+        trajectory.end.y.to_bits(),
+    ]
+}
+
+/// Shifts the trajectory sideways by a pseudorandom distance that is fixed per message, so that
+/// concurrent messages on the same link don't hide each other.
+fn jittered_trajectory(trajectory: &UnderlayLine, message: Entity) -> UnderlayLine {
+    let length = trajectory.length();
+    if length == 0. {
+        return *trajectory;
+    }
+    // Knuth's multiplicative hash, scaled to [-1, 1]
+    let hash = message.id().wrapping_mul(2_654_435_761) >> 22;
+    let offset = (hash as f32 / 1023. * 2. - 1.) * MAX_MESSAGE_JITTER;
+    let direction = trajectory.end - trajectory.start;
+    let normal = UnderlayPosition::new(-direction.y / length, direction.x / length);
+    let shift = normal * offset;
+    UnderlayLine {
+        start: trajectory.start + shift,
+        end: trajectory.end + shift,
+    }
+}
+
+fn point_on_line(line: &UnderlayLine, progress: f32) -> (f32, f32) {
+    let point = UnderlayPosition::lerp(line.start, line.end, progress);
+    (point.x, point.y)
+}
+
+/// A short trail behind an in-flight message that fades out towards its source.
+/// `trail_progress` is the fraction of the trajectory that the trail covers.
+fn view_message_trail(
+    trajectory: &UnderlayLine,
+    progress: f32,
+    trail_progress: f32,
+    color: &str,
+) -> Html {
+    let n_segments = 4;
+    let max_opacity = 0.6;
+
+    if trail_progress <= 0. {
+        return html! {};
+    }
+    let segment_progress = trail_progress / (n_segments as f32);
+    (0..n_segments)
+        .filter_map(|i| {
+            let segment_end = progress - segment_progress * (i as f32);
+            let segment_start = (segment_end - segment_progress).max(0.);
+            (segment_end > 0.).then(|| {
+                let (x1, y1) = point_on_line(trajectory, segment_start);
+                let (x2, y2) = point_on_line(trajectory, segment_end);
+                let opacity = max_opacity * (1. - (i as f32) / (n_segments as f32));
+                html! {
+                    <line
+                        x1={ x1.to_string() }
+                        y1={ y1.to_string() }
+                        x2={ x2.to_string() }
+                        y2={ y2.to_string() }
+                        stroke={ color.to_string() }
+                        stroke-opacity={ opacity.to_string() }
+                        stroke-linecap="round"
+                    />
+                }
+            })
+        })
+        .collect()
 }
 
+/// The fraction of a message's trajectory that it covers within `trail_seconds` of real time.
+fn trail_progress(trail_seconds: RealSeconds, speed: f64, time_span: &TimeSpan) -> f32 {
+    let flight_duration = (time_span.end - time_span.start).into_inner();
+    if flight_duration > 0. {
+        (trail_seconds * speed / flight_duration) as f32
+    } else {
+        0.
+    }
+}
+
+/// How much wider than regular edges the edge with the highest usage is drawn.
+const MAX_EXTRA_EDGE_WIDTH: f32 = 4.;
+/// How far messages sharing a link are moved sideways at most, see `jittered_trajectory`.
+const MAX_MESSAGE_JITTER: f32 = 3.;
+
+/// If more peer sets changed than this, `EdgeMap::rebuild_if_needed` rebuilds all edges instead
+/// of updating only the affected ones.
+const MAX_INCREMENTAL_EDGE_UPDATES: usize = 20;
+
 #[derive(Debug, Default)]
 struct EdgeMap {
     edges: BTreeMap<EdgeEndpoints, (EdgeType, UnderlayLine)>,
+    /// For each node, the other endpoints of its edges (phantom edges included).
+    neighbors: BTreeMap<Entity, BTreeSet<Entity>>,
     last_update: SimSeconds,
 }
 impl EdgeMap {
@@ -394,61 +903,115 @@ impl EdgeMap {
         new
     }
 
+    /// Only updates the edges of nodes whose peer sets changed, unless there are too many of them.
+    /// Starts over if the clock was turned back (e.g., by `Reset`), as the peer sets' update times
+    /// can't be compared with ours then.
     fn rebuild_if_needed(&mut self, world: &World, simtime_now: SimSeconds) -> bool {
-        if self.needs_rebuild(world) {
+        if simtime_now < self.last_update {
+            *self = Self::new(world, simtime_now);
+            return true;
+        }
+        let changed_nodes = self.changed_nodes(world);
+        if changed_nodes.is_empty() {
+            false
+        } else if changed_nodes.len() > MAX_INCREMENTAL_EDGE_UPDATES {
             self.rebuild(world, simtime_now);
             true
         } else {
-            false
+            self.update_edges_of(world, &changed_nodes, simtime_now);
+            true
         }
     }
 
-    fn needs_rebuild(&self, world: &World) -> bool {
+    fn changed_nodes(&self, world: &World) -> Vec<Entity> {
         world
             .query::<&PeerSet>()
             .iter()
-            .any(|(_, peer_set)| peer_set.last_update() > self.last_update)
+            .filter(|(_, peer_set)| peer_set.last_update() > self.last_update)
+            .map(|(node, _)| node)
+            .collect()
     }
 
+    /// Edges that aren't backed by any peer set anymore are kept as phantom edges.
     fn rebuild(&mut self, world: &World, simtime_now: SimSeconds) {
-        let edges = &mut self.edges;
+        log!("Rebuilding edges...");
 
-        for (edge_type, _) in edges.values_mut() {
-            *edge_type = EdgeType::Phantom;
+        // each edge type is derived from both peer sets, so it neither depends on the iteration
+        // order nor on what the edge was before
+        let mut all_edges: BTreeSet<EdgeEndpoints> = self.edges.keys().copied().collect();
+        for (node, peer_set) in world.query::<&PeerSet>().iter() {
+            all_edges.extend(peer_set.iter().map(|&peer| EdgeEndpoints::new(node, peer)));
+        }
+        for endpoints in all_edges {
+            self.update_edge(world, endpoints);
         }
+        self.last_update = simtime_now;
+    }
 
-        log!("Rebuilding edges...");
+    /// Updates all edges (phantom edges included) that have one of `nodes` as endpoint. Returns
+    /// the number of updated edges.
+    fn update_edges_of(
+        &mut self,
+        world: &World,
+        nodes: &[Entity],
+        simtime_now: SimSeconds,
+    ) -> usize {
+        let mut affected_edges = BTreeSet::new();
+        for &node in nodes {
+            if let Ok(peer_set) = world.get::<PeerSet>(node) {
+                affected_edges.extend(peer_set.iter().map(|&peer| EdgeEndpoints::new(node, peer)));
+            }
+            if let Some(neighbors) = self.neighbors.get(&node) {
+                affected_edges.extend(
+                    neighbors
+                        .iter()
+                        .map(|&neighbor| EdgeEndpoints::new(node, neighbor)),
+                );
+            }
+        }
+        for &endpoints in affected_edges.iter() {
+            self.update_edge(world, endpoints);
+        }
+        self.last_update = simtime_now;
+        affected_edges.len()
+    }
 
-        for (node, peer_set) in world.query::<&PeerSet>().iter() {
-            for &peer in peer_set.iter() {
-                let endpoints = EdgeEndpoints::new(node, peer);
-                match edges.entry(endpoints) {
-                    Entry::Occupied(mut e) => {
-                        let e = e.get_mut();
-                        if e.0 == EdgeType::Phantom {
-                            e.0 = if endpoints.left == node {
-                                EdgeType::LeftRight
-                            } else {
-                                EdgeType::RightLeft
-                            };
-                        } else {
-                            e.0 = EdgeType::Undirected;
-                        }
-                    }
-                    Entry::Vacant(e) => {
-                        let _type = if endpoints.left == node {
-                            EdgeType::LeftRight
-                        } else {
-                            EdgeType::RightLeft
-                        };
-                        let line = UnderlayLine::from_nodes(world, node, peer);
-                        e.insert((_type, line));
-                    }
+    fn update_edge(&mut self, world: &World, endpoints: EdgeEndpoints) {
+        let is_peer = |node: Entity, peer: Entity| {
+            world
+                .get::<PeerSet>(node)
+                .is_ok_and(|peer_set| peer_set.contains(&peer))
+        };
+        let edge_type = match (
+            is_peer(endpoints.left, endpoints.right),
+            is_peer(endpoints.right, endpoints.left),
+        ) {
+            (true, true) => EdgeType::Undirected,
+            (true, false) => EdgeType::LeftRight,
+            (false, true) => EdgeType::RightLeft,
+            (false, false) => EdgeType::Phantom,
+        };
+        match UnderlayLine::from_nodes(world, endpoints.left, endpoints.right) {
+            Ok(line) => {
+                self.edges.insert(endpoints, (edge_type, line));
+                self.neighbors
+                    .entry(endpoints.left)
+                    .or_default()
+                    .insert(endpoints.right);
+                self.neighbors
+                    .entry(endpoints.right)
+                    .or_default()
+                    .insert(endpoints.left);
+            }
+            Err(_) => {
+                // one of the nodes was despawned; like `rebuild`, we keep existing edges around
+                if let Some(edge) = self.edges.get_mut(&endpoints) {
+                    edge.0 = edge_type;
                 }
             }
         }
-        self.last_update = simtime_now;
     }
+
     fn edge_type(&self, endpoint1: Entity, endpoint2: Entity) -> Option<EdgeType> {
         self.edges
             .get(&EdgeEndpoints::new(endpoint1, endpoint2))
@@ -491,12 +1054,118 @@ impl EdgeType {
     }
 }
 
-fn blocks_cutout(
-    state: &nakamoto_consensus::NakamotoNodeState,
+type BlockCutout = Vec<Vec<Option<Entity>>>;
+
+/// Remembers each node's block cutout until the node learns about new blocks, so that it isn't
+/// recomputed on every frame. Cutouts of nodes that weren't drawn in the last pass (e.g., because
+/// they were despawned) are dropped, see `evict_unseen`.
+#[derive(Debug, Default)]
+struct BlockCutoutCache {
+    cutouts: RefCell<BTreeMap<Entity, (BlockCutoutKey, Rc<BlockCutout>)>>,
+    seen: RefCell<BTreeSet<Entity>>,
+    recomputations: Cell<usize>,
+}
+/// Any new block changes the number of known blocks, so this is enough for noticing changes of
+/// the tip or the fork tips.
+#[derive(Debug, PartialEq)]
+struct BlockCutoutKey {
+    tip: Entity,
+    fork_count: usize,
+    known_block_count: usize,
     max_depth: usize,
-) -> Vec<Vec<Option<Entity>>> {
+}
+impl BlockCutoutCache {
+    fn get(
+        &self,
+        node: Entity,
+        state: &nakamoto_consensus::NakamotoNodeState,
+        max_depth: usize,
+    ) -> Rc<BlockCutout> {
+        let key = BlockCutoutKey {
+            tip: state.tip(),
+            fork_count: state.fork_tips().len(),
+            known_block_count: state.known_block_count(),
+            max_depth,
+        };
+        self.seen.borrow_mut().insert(node);
+        let mut cutouts = self.cutouts.borrow_mut();
+        match cutouts.get(&node) {
+            Some((cached_key, cutout)) if *cached_key == key => cutout.clone(),
+            _ => {
+                self.recomputations.set(self.recomputations.get() + 1);
+                let cutout = Rc::new(blocks_cutout(state, max_depth));
+                cutouts.insert(node, (key, cutout.clone()));
+                cutout
+            }
+        }
+    }
+    /// Drops the cutouts of all nodes that weren't passed to `get` since the last call.
+    fn evict_unseen(&self) {
+        let seen = std::mem::take(&mut *self.seen.borrow_mut());
+        self.cutouts
+            .borrow_mut()
+            .retain(|node, _| seen.contains(node));
+    }
+}
+
+/// How long nodes are highlighted after a reorg, in real seconds.
+const REORG_FLASH_DURATION: RealSeconds = 1.5;
+
+/// Remembers each node's tip for noticing reorgs, i.e., nodes switching to a tip that doesn't
+/// extend their previous one, and when each node's most recent reorg was noticed (and whether it
+/// reverted confirmed transactions, see `NakamotoNodeState::reverted_txes`).
+#[derive(Debug, Default)]
+struct ReorgFlashes {
+    tips: BTreeMap<Entity, Entity>,
+    reverted_tx_counts: BTreeMap<Entity, usize>,
+    flashes: BTreeMap<Entity, (RealSeconds, bool)>,
+    now: RealSeconds,
+}
+impl ReorgFlashes {
+    /// Also forgets nodes that have no `NakamotoNodeState` anymore, e.g., after a `Reset`.
+    fn update(&mut self, world: &World, now: RealSeconds) {
+        self.now = now;
+        let mut seen = BTreeSet::new();
+        for (node, state) in world
+            .query::<&nakamoto_consensus::NakamotoNodeState>()
+            .iter()
+        {
+            seen.insert(node);
+            let tip = state.tip();
+            let reverted_tx_count = state.reverted_tx_count();
+            let old_reverted_tx_count = self
+                .reverted_tx_counts
+                .insert(node, reverted_tx_count)
+                .unwrap_or(0);
+            if let Some(old_tip) = self.tips.insert(node, tip) {
+                if state.common_ancestor(old_tip, tip) != Some(old_tip) {
+                    self.flashes
+                        .insert(node, (now, reverted_tx_count > old_reverted_tx_count));
+                }
+            }
+        }
+        self.tips.retain(|node, _| seen.contains(node));
+        self.reverted_tx_counts
+            .retain(|node, _| seen.contains(node));
+        self.flashes
+            .retain(|node, (start, _)| seen.contains(node) && now - *start < REORG_FLASH_DURATION);
+    }
+    /// From `1.` right after a reorg down to `0.` once the flash is over.
+    fn intensity(&self, node: Entity) -> f64 {
+        self.flashes.get(&node).map_or(0., |(start, _)| {
+            (1. - (self.now - start) / REORG_FLASH_DURATION).max(0.)
+        })
+    }
+    /// Whether the node's most recent (still flashing) reorg reverted confirmed transactions.
+    fn reverted_confirmed_txes(&self, node: Entity) -> bool {
+        matches!(self.flashes.get(&node), Some(&(_, true)))
+    }
+}
+
+fn blocks_cutout(state: &nakamoto_consensus::NakamotoNodeState, max_depth: usize) -> BlockCutout {
     let mut main_chain = vec![];
-    let mut block_id = state.tip();
+    // the genesis block isn't shown
+    let mut block_id = Some(state.tip()).filter(|_| !state.is_at_genesis());
     for _ in 0..max_depth {
         if block_id == None {
             break;
@@ -508,12 +1177,7 @@ fn blocks_cutout(
     for (fork_height_diff, mut block_id) in state
         .fork_tips()
         .iter()
-        .map(|&ft| {
-            (
-                state.tip_height() - state.block_header(ft).unwrap().height,
-                Some(ft),
-            )
-        })
+        .map(|&ft| (state.fork_depth(ft), Some(ft)))
         .filter(|(height_diff, _)| *height_diff < max_depth)
     {
         result.push(vec![None; fork_height_diff]);
@@ -535,6 +1199,89 @@ mod tests {
 
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
 
+    #[wasm_bindgen_test]
+    fn scaled_node_radius_is_bounded() {
+        use nakamoto_consensus::MiningPower;
+        assert_eq!(5., scaled_node_radius(5., None));
+        assert_eq!(10., scaled_node_radius(5., Some(MiningPower(4.))));
+        assert_eq!(2.5, scaled_node_radius(5., Some(MiningPower(0.))));
+        assert_eq!(15., scaled_node_radius(5., Some(MiningPower(1000.))));
+    }
+
+    #[wasm_bindgen_test]
+    fn custom_node_colors_are_used_as_fill() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        assert_eq!(
+            "black",
+            node_fill(sim.world.get::<NodeColor>(node).ok().as_deref())
+        );
+        sim.do_now(SetNodeColor(node, "purple".to_string()));
+        sim.catch_up(1.);
+        assert_eq!(
+            "purple",
+            node_fill(sim.world.get::<NodeColor>(node).ok().as_deref())
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn trails_cover_the_distance_flown_in_trail_seconds() {
+        let time_span = TimeSpan {
+            start: OrderedFloat(1.),
+            end: OrderedFloat(1.2),
+        };
+        assert_eq!(0.5, trail_progress(1., 0.1, &time_span));
+        assert_eq!(0., trail_progress(0., 0.1, &time_span));
+        let instant = TimeSpan {
+            start: OrderedFloat(1.),
+            end: OrderedFloat(1.),
+        };
+        assert_eq!(0., trail_progress(1., 0.1, &instant));
+    }
+
+    #[wasm_bindgen_test]
+    fn latency_colors_go_from_green_to_red() {
+        let max_latency = OrderedFloat(0.2);
+        assert_eq!(
+            "hsl(120, 80%, 40%)",
+            latency_color(OrderedFloat(0.), max_latency)
+        );
+        assert_eq!(
+            "hsl(60, 80%, 40%)",
+            latency_color(OrderedFloat(0.1), max_latency)
+        );
+        assert_eq!("hsl(0, 80%, 40%)", latency_color(max_latency, max_latency));
+        assert_eq!(
+            "hsl(120, 80%, 40%)",
+            latency_color(OrderedFloat(0.), OrderedFloat(0.))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn jittered_trajectories_are_parallel_and_fixed_per_message() {
+        let mut world = World::new();
+        let trajectory = UnderlayLine {
+            start: UnderlayPosition::new(0., 0.),
+            end: UnderlayPosition::new(100., 0.),
+        };
+        let offsets: Vec<f32> = (0..10)
+            .map(|_| {
+                let jittered = jittered_trajectory(&trajectory, world.spawn(()));
+                assert_eq!((0., 100.), (jittered.start.x, jittered.end.x));
+                assert_eq!(jittered.start.y, jittered.end.y);
+                assert!(jittered.start.y.abs() <= MAX_MESSAGE_JITTER);
+                jittered.start.y
+            })
+            .collect();
+        assert!(offsets.iter().any(|&offset| offset != offsets[0]));
+
+        let message = world.spawn(());
+        assert_eq!(
+            jittered_trajectory(&trajectory, message).start,
+            jittered_trajectory(&trajectory, message).start
+        );
+    }
+
     #[wasm_bindgen_test]
     fn rebuild_builds_edges() {
         let mut world = World::default();
@@ -572,6 +1319,51 @@ mod tests {
         );
     }
 
+    #[wasm_bindgen_test]
+    fn edge_types_follow_peer_sets_across_rebuilds() {
+        let mut world = World::default();
+        let mut edges = EdgeMap::default();
+        let node1 = world.spawn((PeerSet::default(), UnderlayPosition::new(23., 42.)));
+        let node2 = world.spawn((
+            PeerSet::default_from(vec![node1]),
+            UnderlayPosition::new(13., 13.),
+        ));
+        let expected_one_directional = if EdgeEndpoints::new(node1, node2).left == node2 {
+            EdgeType::LeftRight
+        } else {
+            EdgeType::RightLeft
+        };
+
+        // one-directional links stay one-directional
+        for _ in 0..3 {
+            edges.rebuild(&world, Default::default());
+            assert_eq!(
+                Some(expected_one_directional),
+                edges.edge_type(node1, node2)
+            );
+        }
+
+        world
+            .query_one_mut::<&mut PeerSet>(node1)
+            .unwrap()
+            .insert(node2, Default::default());
+        for _ in 0..2 {
+            edges.rebuild(&world, Default::default());
+            assert_eq!(Some(EdgeType::Undirected), edges.edge_type(node1, node2));
+        }
+
+        // and bidirectional ones become one-directional again
+        world
+            .query_one_mut::<&mut PeerSet>(node1)
+            .unwrap()
+            .remove(&node2, Default::default());
+        edges.rebuild(&world, Default::default());
+        assert_eq!(
+            Some(expected_one_directional),
+            edges.edge_type(node1, node2)
+        );
+    }
+
     #[wasm_bindgen_test]
     fn rebuild_stores_removed_edges_as_phantom_edges() {
         let mut world = World::default();
@@ -600,4 +1392,181 @@ mod tests {
                 .0,
         );
     }
+
+    #[wasm_bindgen_test]
+    fn incremental_update_matches_full_rebuild_on_large_network() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(200));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.work_until(OrderedFloat(1.));
+
+        let mut edges = EdgeMap::new(&sim.world, sim.time.now());
+        let n_edges = edges.edges.len();
+        sim.work_until(OrderedFloat(2.));
+
+        let node = sim.pick_random_node().unwrap();
+        let peer = *sim.peers_mut(node).iter().next().unwrap();
+        let other_node = sim.pick_random_other_node(node).unwrap();
+        sim.remove_peer(node, peer);
+        sim.add_peer(node, other_node);
+        sim.work_until(OrderedFloat(3.));
+
+        let changed_nodes = edges.changed_nodes(&sim.world);
+        assert_eq!(vec![node], changed_nodes);
+        let n_updated_edges = edges.update_edges_of(&sim.world, &changed_nodes, sim.time.now());
+        assert!(n_updated_edges * 10 < n_edges);
+
+        let mut rebuilt_edges = EdgeMap::default();
+        rebuilt_edges.rebuild(&sim.world, sim.time.now());
+        for (endpoints, (edge_type, _)) in rebuilt_edges.edges.iter() {
+            assert_eq!(
+                Some(*edge_type),
+                edges.edge_type(endpoints.left, endpoints.right)
+            );
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn edges_are_rebuilt_after_reset() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(3));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.work_until(OrderedFloat(10.));
+        let mut edges = EdgeMap::new(&sim.world, sim.time.now());
+        assert_eq!(3, edges.edges.len());
+
+        sim.do_now(Reset);
+        sim.process_next_event();
+        sim.do_now(SpawnRandomNodes(2));
+        sim.work_until(OrderedFloat(1.5));
+        let nodes = sim.all_nodes();
+        sim.add_peer(nodes[0], nodes[1]);
+        sim.add_peer(nodes[1], nodes[0]);
+        sim.work_until(OrderedFloat(2.));
+
+        assert!(edges.rebuild_if_needed(&sim.world, sim.time.now()));
+        assert_eq!(1, edges.edges.len());
+        assert_eq!(
+            Some(EdgeType::Undirected),
+            edges.edge_type(nodes[0], nodes[1])
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn blocks_cutout_honors_max_depth() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(
+            nakamoto_consensus::NakamotoConsensus::default(),
+        ));
+        let node = sim.spawn_random_node();
+        for _ in 0..12 {
+            sim.do_now(ForSpecific(node, nakamoto_consensus::MineBlock));
+        }
+        sim.catch_up(10.);
+
+        let state = sim
+            .world
+            .get::<nakamoto_consensus::NakamotoNodeState>(node)
+            .unwrap();
+
+        assert_eq!(5, blocks_cutout(&state, 5)[0].len());
+        assert_eq!(10, blocks_cutout(&state, 10)[0].len());
+    }
+
+    #[wasm_bindgen_test]
+    fn block_cutouts_are_only_recomputed_when_tip_changes() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(
+            nakamoto_consensus::NakamotoConsensus::default(),
+        ));
+        let node = sim.spawn_random_node();
+        sim.do_now(ForSpecific(node, nakamoto_consensus::MineBlock));
+        sim.catch_up(10.);
+
+        let cache = BlockCutoutCache::default();
+        {
+            let state = sim
+                .world
+                .get::<nakamoto_consensus::NakamotoNodeState>(node)
+                .unwrap();
+            for _ in 0..100 {
+                cache.get(node, &state, 5);
+            }
+        }
+        assert_eq!(1, cache.recomputations.get());
+
+        sim.do_now(ForSpecific(node, nakamoto_consensus::MineBlock));
+        sim.catch_up(10.);
+        let state = sim
+            .world
+            .get::<nakamoto_consensus::NakamotoNodeState>(node)
+            .unwrap();
+        for _ in 0..100 {
+            cache.get(node, &state, 5);
+        }
+        assert_eq!(2, cache.recomputations.get());
+        assert_eq!(2, cache.get(node, &state, 5)[0].len());
+
+        cache.evict_unseen();
+        assert_eq!(1, cache.cutouts.borrow().len());
+        cache.evict_unseen();
+        assert!(cache.cutouts.borrow().is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn reorgs_flash_until_they_fade() {
+        use nakamoto_consensus::{MineBlock, ResyncAll};
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(
+            nakamoto_consensus::NakamotoConsensus::default(),
+        ));
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        let mut flashes = ReorgFlashes::default();
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(10.);
+        flashes.update(&sim.world, 0.);
+        // extending the own chain isn't a reorg
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(10.);
+        flashes.update(&sim.world, 0.1);
+        assert_eq!(0., flashes.intensity(node2));
+
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        sim.do_now(ResyncAll);
+        sim.catch_up(10.);
+        flashes.update(&sim.world, 1.);
+        assert_eq!(1., flashes.intensity(node1));
+        assert_eq!(0., flashes.intensity(node2));
+        assert!(!flashes.reverted_confirmed_txes(node1));
+
+        flashes.update(&sim.world, 1. + REORG_FLASH_DURATION / 2.);
+        assert_eq!(0.5, flashes.intensity(node1));
+        flashes.update(&sim.world, 1. + REORG_FLASH_DURATION);
+        assert_eq!(0., flashes.intensity(node1));
+    }
+
+    #[wasm_bindgen_test]
+    fn reorg_flashes_forget_vanished_nodes() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(
+            nakamoto_consensus::NakamotoConsensus::default(),
+        ));
+        let node = sim.spawn_random_node();
+        sim.do_now(ForSpecific(node, nakamoto_consensus::MineBlock));
+        sim.catch_up(10.);
+        let mut flashes = ReorgFlashes::default();
+        flashes.update(&sim.world, 0.);
+        assert_eq!(1, flashes.tips.len());
+        assert_eq!(1, flashes.reverted_tx_counts.len());
+
+        sim.do_now(Reset);
+        sim.process_next_event();
+        flashes.update(&sim.world, 0.1);
+        assert!(flashes.tips.is_empty());
+        assert!(flashes.reverted_tx_counts.is_empty());
+    }
 }
@@ -579,9 +579,7 @@ impl TransactionsCache {
         }
     }
     fn update_confirmed(&mut self, state: &NakamotoNodeState, sim: &Simulation) -> bool {
-        let new_tip = state
-            .tip()
-            .map(|block_id| state.block_header(block_id).unwrap());
+        let new_tip = (!state.is_at_genesis()).then(|| state.tip_header());
         if self.tip == new_tip {
             false // nothing changed
         } else {
@@ -1,12 +1,17 @@
 #![allow(clippy::wildcard_imports)]
 #![macro_use]
 pub use gloo::console::log;
+#[cfg(feature = "ui")]
 use gloo::render::{request_animation_frame, AnimationFrame};
 
+#[cfg(feature = "ui")]
 use yew::prelude::*;
+#[cfg(feature = "ui")]
 use yew::virtual_dom::AttrValue;
 
+#[cfg(feature = "ui")]
 mod components;
+#[cfg(feature = "ui")]
 pub use components::*;
 
 mod protocols;
@@ -15,6 +20,7 @@ pub use protocols::*;
 mod simulation;
 pub use simulation::*;
 
+#[cfg(feature = "ui")]
 pub struct Isds {
     pub sim: SharedSimulation,
     last_render: RealSeconds,
@@ -22,6 +28,7 @@ pub struct Isds {
     _render_loop_handle: Option<AnimationFrame>,
 }
 
+#[cfg(feature = "ui")]
 #[derive(Debug, Clone, PartialEq)]
 pub struct IsdsContext {
     pub sim: SharedSimulation,
@@ -29,11 +36,13 @@ pub struct IsdsContext {
     pub highlight: components::common::Highlight,
 }
 
+#[cfg(feature = "ui")]
 #[derive(Debug, Clone)]
 pub enum Msg {
     Rendered(RealSeconds),
 }
 
+#[cfg(feature = "ui")]
 #[derive(Properties, PartialEq)]
 pub struct Props {
     #[prop_or_default]
@@ -42,6 +51,7 @@ pub struct Props {
     pub sim: SharedSimulation,
 }
 
+#[cfg(feature = "ui")]
 impl Component for Isds {
     type Message = Msg;
     type Properties = Props;
@@ -1,5 +1,6 @@
 use super::*;
 
+pub mod naive_longest_chain;
 pub mod nakamoto_consensus;
 pub mod random_walks;
 pub mod simple_flooding;
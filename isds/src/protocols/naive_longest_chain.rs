@@ -0,0 +1,139 @@
+use super::*;
+use nakamoto_consensus::InventoryItem;
+use simple_flooding::*;
+
+/// A deliberately broken counterpart to `NakamotoConsensus`, e.g., for showing students why
+/// nodes have to compare forks: each node simply switches to whichever block it has seen last,
+/// without looking at heights or keeping track of forks. Pokes mine a new (empty) block on top of
+/// the node's tip. Nodes that were split don't necessarily agree again once they are reconnected.
+#[derive(Debug, Default)]
+pub struct NaiveLongestChain {
+    flooding: SimpleFlooding<InventoryItem>,
+}
+impl NaiveLongestChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn handle_mining_success(node: &mut NodeInterface) -> Result<(), Box<dyn Error>> {
+        let tip = node.get::<NaiveChainState>().tip;
+        let block_header = if tip.is_some() {
+            node.spawn_block(tip, [])
+        } else {
+            node.spawn_first_block(None, [])
+        };
+        node.get::<NaiveChainState>().tip = Some(block_header.id);
+        node.log_at(
+            LogLevel::Normal,
+            &format!(
+                "Mined block {} at height {}.",
+                block_header.short_id(),
+                block_header.height
+            ),
+        );
+        SimpleFlooding::flood(node, InventoryItem::Block(block_header.id));
+        Ok(())
+    }
+}
+
+/// The only thing a `NaiveLongestChain` node remembers: the last block it has seen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NaiveChainState {
+    /// `None` for the (virtual) genesis block.
+    pub tip: Option<Entity>,
+}
+
+impl Protocol for NaiveLongestChain {
+    type MessagePayload = SimpleFloodingMessage<InventoryItem>;
+
+    fn handle_message(
+        &self,
+        mut node: NodeInterface,
+        underlay_message: UnderlayMessage,
+        message_payload: Self::MessagePayload,
+    ) -> Result<(), Box<dyn Error>> {
+        if let InventoryItem::Block(block_id) = message_payload.0 {
+            // no height comparison at all: the latest block always wins
+            node.get::<NaiveChainState>().tip = Some(block_id);
+        }
+        self.flooding
+            .handle_message(node, underlay_message, message_payload)
+    }
+
+    fn node_status(&self, mut node: NodeInterface) -> String {
+        let tip = node
+            .try_get::<NaiveChainState>()
+            .and_then(|state| state.tip);
+        let height = tip
+            .and_then(|tip| node.get_block_header(tip).map(|header| header.height))
+            .unwrap_or(0);
+        format!("h={}", height)
+    }
+
+    fn handle_poke(&self, mut node: NodeInterface) -> Result<(), Box<dyn Error>> {
+        Self::handle_mining_success(&mut node)
+    }
+
+    fn handle_peer_set_update(
+        &self,
+        mut node: NodeInterface,
+        update: PeerSetUpdate,
+    ) -> Result<(), Box<dyn Error>> {
+        match update {
+            PeerSetUpdate::PeerAdded(peer) => {
+                if let Some(tip) = node.get::<NaiveChainState>().tip {
+                    SimpleFlooding::flood_peer_with(&mut node, peer, [InventoryItem::Block(tip)]);
+                }
+            }
+            PeerSetUpdate::PeerRemoved(peer) => {
+                SimpleFlooding::<InventoryItem>::forget_peer(&mut node, peer);
+            }
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    fn tip(sim: &Simulation, node: Entity) -> Option<Entity> {
+        sim.world.get::<NaiveChainState>(node).unwrap().tip
+    }
+
+    /// The counterpart to `nakamoto_consensus_recovers_from_splits`.
+    #[wasm_bindgen_test]
+    fn naive_longest_chain_does_not_recover_from_splits() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NaiveLongestChain::new()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        sim.do_now(PokeSpecificNode(node1));
+        sim.do_now(PokeSpecificNode(node1));
+        sim.do_now(PokeSpecificNode(node1));
+
+        sim.do_now(PokeSpecificNode(node2));
+        sim.do_now(PokeSpecificNode(node2));
+
+        sim.catch_up(10.);
+        let tip1 = tip(&sim, node1);
+        let tip2 = tip(&sim, node2);
+
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+
+        sim.catch_up(10.);
+
+        // the nodes just swapped their tips, node1 even gave up its longer chain
+        assert_eq!(tip2, tip(&sim, node1));
+        assert_eq!(tip1, tip(&sim, node2));
+        let agree = |sim: &Simulation| tip(sim, node1) == tip(sim, node2);
+        assert!(sim
+            .work_until_condition(agree, OrderedFloat(100.))
+            .is_none());
+    }
+}
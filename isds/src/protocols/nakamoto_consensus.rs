@@ -1,8 +1,9 @@
 use super::*;
 use simple_flooding::*;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use blockchain_types::*;
+use rand_distr::{Distribution, Exp};
 
 #[derive(Debug, Clone)]
 pub struct BuildAndBroadcastTransaction {
@@ -43,6 +44,84 @@ impl EntityAction for MineBlock {
     }
 }
 
+/// A convenience command for explicitly mining a block at a specific node, independently of how
+/// pokes are interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MineBlockAtNode(pub Entity);
+impl Command for MineBlockAtNode {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        ForSpecific(self.0, MineBlock).execute(sim)
+    }
+}
+
+const MINING_PROCESS: &str = "mining";
+
+/// Keeps mining blocks at random nodes (weighted by their `MiningPower`) with exponentially
+/// distributed intervals, i.e., `rate` blocks per sim second on average across the network.
+/// Replaces mining started earlier; see `StopMining`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StartMining {
+    pub rate: f64,
+}
+impl Command for StartMining {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        let process = sim.start_process(MINING_PROCESS);
+        MiningRound {
+            process,
+            rate: self.rate,
+        }
+        .schedule_next(sim)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopMining;
+impl Command for StopMining {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.stop_process(MINING_PROCESS);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MiningRound {
+    process: Entity,
+    rate: f64,
+}
+impl MiningRound {
+    fn schedule_next(self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        let interval = Exp::new(self.rate)?.sample(sim.rng_mut());
+        sim.do_in(OrderedFloat(interval), self);
+        Ok(())
+    }
+}
+impl Command for MiningRound {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        if !sim.is_process_running(self.process) {
+            return Ok(());
+        }
+        let miner = sim.pick_random_node_weighted(MiningPower::of);
+        if let Some(miner) = miner {
+            ForSpecific(miner, MineBlock).execute(sim)?;
+        }
+        self.schedule_next(sim)
+    }
+}
+
+/// Changes a node's `MiningPower`, e.g., for letting a pool gain the majority mid-demo. Mining
+/// power can't be negative.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SetMiningPower(pub Entity, pub f64);
+impl Command for SetMiningPower {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        if self.1.is_nan() || self.1 < 0. {
+            return Err(format!("Invalid mining power: {}", self.1).into());
+        }
+        sim.insert_component(self.0, MiningPower(self.1))?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MineBlockWithLimit(pub usize);
 impl EntityAction for MineBlockWithLimit {
@@ -53,10 +132,213 @@ impl EntityAction for MineBlockWithLimit {
     }
 }
 
+/// Lets a node start over on its own blockchain, building upon the given genesis block (see
+/// `Simulation::spawn_genesis_block`). The node will ignore blocks of all other blockchains, so
+/// groups of nodes on different genesis blocks never converge.
+#[derive(Debug, Clone)]
+pub struct UseGenesisBlock(pub Entity);
+impl EntityAction for UseGenesisBlock {
+    fn execute_for(&self, sim: &mut Simulation, entity: Entity) -> Result<(), Box<dyn Error>> {
+        sim.world
+            .get::<GenesisBlock>(self.0)
+            .map_err(|_| "Not a genesis block?")?;
+        sim.insert_component(entity, NakamotoNodeState::new_with_genesis(self.0))?;
+        Ok(())
+    }
+}
+
+/// Feeds a fully specified (e.g., recorded) block into a node, which then handles it like a block
+/// it received from a peer. Use a freshly reserved entity as the block id.
+#[derive(Debug, Clone)]
+pub struct InjectBlock {
+    pub node: Entity,
+    pub header: BlockHeader,
+    pub contents: BlockContents,
+}
+impl Command for InjectBlock {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        let mut node = sim.node_interface(self.node);
+        node.import_block(self.header, self.contents.clone())?;
+        NakamotoConsensus::register_block(&mut node, self.header, self.contents.clone());
+        if NakamotoConsensus::state(&mut node)
+            .block_header(self.header.id)
+            .is_some()
+        {
+            SimpleFlooding::flood(&mut node, InventoryItem::Block(self.header.id));
+        } else {
+            node.log("Ignoring injected block that doesn't fit into my blockchain.");
+        }
+        Ok(())
+    }
+}
+
+/// Re-floods all peers of a node with all blocks the node knows about.
+#[derive(Debug, Clone)]
+pub struct ResyncPeers;
+impl EntityAction for ResyncPeers {
+    fn execute_for(&self, sim: &mut Simulation, entity: Entity) -> Result<(), Box<dyn Error>> {
+        let mut node = sim.node_interface(entity);
+        let peers = node.get::<PeerSet>().clone();
+        for peer in peers.into_iter() {
+            NakamotoConsensus::handle_peer_added(&mut node, peer)?;
+        }
+        Ok(())
+    }
+}
+
+/// A convenience command for bringing all nodes into agreement quickly, e.g., after the topology
+/// was edited manually.
+#[derive(Debug, Clone)]
+pub struct ResyncAll;
+impl Command for ResyncAll {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        ForEachNode(ResyncPeers).execute(sim)
+    }
+}
+
+/// An overview of a node's state, see `Simulation::state_table`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeSummary {
+    pub node: Entity,
+    pub name: String,
+    pub peer_count: usize,
+    pub tip_height: usize,
+    /// `None` if the node is still on the genesis block.
+    pub tip_short_id: Option<String>,
+    pub fork_count: usize,
+}
+
+impl Simulation {
+    /// Summarizes the state of every node, sorted by name. Nodes that haven't seen any block yet
+    /// are on the genesis block.
+    pub fn state_table(&self) -> Vec<NodeSummary> {
+        let mut table: Vec<NodeSummary> = self
+            .world
+            .query::<(
+                &UnderlayNodeName,
+                Option<&PeerSet>,
+                Option<&NakamotoNodeState>,
+            )>()
+            .iter()
+            .map(|(node, (name, peers, state))| NodeSummary {
+                node,
+                name: name.0.clone(),
+                peer_count: peers.map_or(0, PeerSet::len),
+                tip_height: state.map_or(0, NakamotoNodeState::tip_height),
+                tip_short_id: state
+                    .filter(|state| !state.is_at_genesis())
+                    .map(|state| short_hash(state.tip())),
+                fork_count: state.map_or(0, |state| state.fork_tips().len()),
+            })
+            .collect();
+        table.sort_by(|summary1, summary2| summary1.name.cmp(&summary2.name));
+        table
+    }
+    /// The node's tip, i.e., the default genesis block for nodes without a `NakamotoNodeState`.
+    fn tip_of(&self, state: Option<&NakamotoNodeState>) -> Entity {
+        state.map_or(self.default_genesis_block(), NakamotoNodeState::tip)
+    }
+    /// Whether all nodes agree on the same tip. Nodes that haven't seen any block yet count as
+    /// being on the genesis block.
+    pub fn is_converged(&self) -> bool {
+        let mut query = self
+            .world
+            .query::<Option<&NakamotoNodeState>>()
+            .with::<UnderlayNodeName>();
+        let mut tips = query.iter().map(|(_, state)| self.tip_of(state));
+        match tips.next() {
+            Some(first_tip) => tips.all(|tip| tip == first_tip),
+            None => true,
+        }
+    }
+    /// How many nodes are on each tip, e.g., for showing how much of the network agrees. Nodes
+    /// that haven't seen any block yet count as being on the genesis block.
+    pub fn tip_agreement(&self) -> BTreeMap<Entity, usize> {
+        let mut agreement = BTreeMap::new();
+        let mut query = self
+            .world
+            .query::<Option<&NakamotoNodeState>>()
+            .with::<UnderlayNodeName>();
+        for (_, state) in query.iter() {
+            *agreement.entry(self.tip_of(state)).or_default() += 1;
+        }
+        agreement
+    }
+    /// Checks that all nodes are on the same main chain, and that each node's main chain leads
+    /// back to the genesis block through known blocks of consecutive heights. On failure, the
+    /// error lists which nodes (by name) have an inconsistent chain or diverge from the others
+    /// and at which height, e.g., for test assertions.
+    pub fn consensus_check(&self) -> Result<(), String> {
+        let mut problems = vec![];
+        let mut chains = vec![];
+        for (_, (name, state)) in self
+            .world
+            .query::<(&UnderlayNodeName, Option<&NakamotoNodeState>)>()
+            .iter()
+        {
+            let default_chain = || Ok(vec![self.default_genesis_block()]);
+            match state.map_or_else(default_chain, NakamotoNodeState::checked_main_chain) {
+                Ok(chain) => chains.push((name.0.clone(), chain)),
+                Err(e) => problems.push(format!("{}: {}", name.0, e)),
+            }
+        }
+        chains.sort();
+        if let Some((reference_name, reference_chain)) = chains.first() {
+            for (name, chain) in chains.iter().skip(1) {
+                if chain != reference_chain {
+                    let common_blocks = chain
+                        .iter()
+                        .zip(reference_chain.iter())
+                        .take_while(|(block, reference_block)| block == reference_block)
+                        .count();
+                    // the main chain starts at height 0
+                    problems.push(format!(
+                        "{}: diverges from {} at height {}",
+                        name, reference_name, common_blocks
+                    ));
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+    /// The number of distinct transactions that any node saw being reverted by a reorg, see
+    /// `NakamotoNodeState::reverted_txes`.
+    pub fn reverted_tx_count(&self) -> usize {
+        self.world
+            .query::<&NakamotoNodeState>()
+            .iter()
+            .flat_map(|(_, state)| state.reverted_txes.iter().copied())
+            .collect::<HashSet<Entity>>()
+            .len()
+    }
+    /// Runs the simulation until all nodes agree on the same tip or until `timeout` sim seconds
+    /// have passed. Returns the elapsed sim time if the nodes converged in time.
+    pub fn work_until_converged(&mut self, timeout: SimSeconds) -> Option<SimSeconds> {
+        self.work_until_condition(Simulation::is_converged, timeout)
+    }
+    /// Runs the simulation until the next block is mined anywhere in the network (e.g., by
+    /// `StartMining`) or until `timeout` sim seconds have passed. Returns the elapsed sim time if
+    /// a block was mined in time.
+    pub fn step_to_next_block(&mut self, timeout: SimSeconds) -> Option<SimSeconds> {
+        let block_count = |sim: &Simulation| sim.world.query::<&BlockHeader>().iter().count();
+        let blocks_before = block_count(self);
+        self.work_until_condition(move |sim| block_count(sim) > blocks_before, timeout)
+    }
+}
+
+/// Rough sizes in bytes, loosely based on Bitcoin's.
+const BLOCK_HEADER_SIZE: usize = 80;
+const TRANSACTION_SIZE: usize = 250;
+
 #[derive(Debug, Default)]
 pub struct NakamotoConsensus {
     flooding: SimpleFlooding<InventoryItem>,
     block_limit: Option<usize>,
+    genesis: Option<Entity>,
 }
 impl NakamotoConsensus {
     pub fn new() -> Self {
@@ -65,17 +347,59 @@ impl NakamotoConsensus {
             /// Currently, the block size limit set here is only used when a block is mined
             /// following a `poke`.
             block_limit: None,
+            genesis: None,
         }
     }
     pub fn new_with_block_limit(block_limit: usize) -> Self {
         Self {
             flooding: SimpleFlooding::new(),
             block_limit: Some(block_limit),
+            genesis: None,
+        }
+    }
+    /// Nodes spawned from now on build upon the given genesis block (see `UseGenesisBlock`).
+    pub fn new_with_genesis(genesis: Entity) -> Self {
+        Self {
+            genesis: Some(genesis),
+            ..Self::new()
+        }
+    }
+    /// Meant to be passed to `Simulation::set_message_speed_factor`. Lets blocks travel slower
+    /// than transactions, the more so the larger they are (see `block_size`).
+    pub fn message_speed_factor(message: Entity, world: &World) -> f64 {
+        match world.get::<SimpleFloodingMessage<InventoryItem>>(message) {
+            Ok(message) => match message.0 {
+                InventoryItem::Transaction(_) => 1.,
+                InventoryItem::Block(block_id) => {
+                    let payload_size = Self::block_size(block_id, world) - BLOCK_HEADER_SIZE;
+                    0.5 / (1. + 0.1 * payload_size as f64 / TRANSACTION_SIZE as f64)
+                }
+            },
+            Err(_) => 1.,
+        }
+    }
+    /// Meant to be passed to `BandwidthMeter::new`.
+    pub fn message_size(message: Entity, world: &World) -> usize {
+        match world.get::<SimpleFloodingMessage<InventoryItem>>(message) {
+            Ok(message) => match message.0 {
+                InventoryItem::Transaction(_) => TRANSACTION_SIZE,
+                InventoryItem::Block(block_id) => Self::block_size(block_id, world),
+            },
+            Err(_) => 0,
         }
     }
+    /// The size of a block in bytes, derived from the transactions it contains.
+    pub fn block_size(block_id: Entity, world: &World) -> usize {
+        let n_txes = world.get::<BlockContents>(block_id).map_or(0, |c| c.len());
+        BLOCK_HEADER_SIZE + TRANSACTION_SIZE * n_txes
+    }
+    /// The node's state, which starts on the default genesis block unless `on_node_spawned` set
+    /// up another one.
+    fn state<'n>(node: &'n mut NodeInterface) -> &'n mut NakamotoNodeState {
+        node.get_or_insert_with(NakamotoNodeState::new)
+    }
     fn handle_transaction(node: &mut NodeInterface, tx_id: Entity) -> Result<(), Box<dyn Error>> {
-        node.get::<NakamotoNodeState>()
-            .register_transaction_id(tx_id);
+        Self::state(node).register_transaction_id(tx_id);
         Ok(())
     }
     fn handle_block(node: &mut NodeInterface, block_id: Entity) -> Result<(), Box<dyn Error>> {
@@ -83,10 +407,51 @@ impl NakamotoConsensus {
             .get_block(block_id)
             .ok_or("Received a block that doesn't exist!")?;
         let block_contents = block_contents.clone();
-        node.get::<NakamotoNodeState>()
-            .register_block(block_header, block_contents);
+        Self::register_block(node, block_header, block_contents);
         Ok(())
     }
+    /// Registers the block with the node's state (see `NakamotoNodeState::register_block`) and
+    /// looks for reverted transactions if the node switched to another tip. Returns whether it
+    /// did.
+    fn register_block(
+        node: &mut NodeInterface,
+        block_header: BlockHeader,
+        block_contents: BlockContents,
+    ) -> bool {
+        let old_tip = Self::state(node).tip;
+        let tip_updated = Self::state(node).register_block(block_header, block_contents);
+        if tip_updated {
+            Self::detect_reverted_txes(node, old_tip);
+        }
+        tip_updated
+    }
+    /// Looks for transactions that were confirmed in blocks orphaned by switching away from
+    /// `old_tip`, and that aren't part of the new main chain. Each of them is logged and reported
+    /// to event handlers (see `NodeEvent::TransactionReverted`).
+    fn detect_reverted_txes(node: &mut NodeInterface, old_tip: Entity) {
+        let orphaned_blocks = Self::state(node).fork_blocks(old_tip);
+        for block_id in orphaned_blocks {
+            let block_contents = match node.get_block_contents(block_id) {
+                Some(block_contents) => block_contents.clone(),
+                None => continue,
+            };
+            for tx_id in block_contents.into_iter() {
+                let state = Self::state(node);
+                if state.confirmations(tx_id).is_none() && !state.reverted_txes.contains(&tx_id) {
+                    state.reverted_txes.push(tx_id);
+                    node.report_reverted_transaction(tx_id);
+                    node.log_at(
+                        LogLevel::Normal,
+                        &format!(
+                            "Transaction {} was reverted with block {}.",
+                            short_hash(tx_id),
+                            short_hash(block_id)
+                        ),
+                    );
+                }
+            }
+        }
+    }
     fn handle_new_transaction(
         node: &mut NodeInterface,
         from: Address,
@@ -98,8 +463,7 @@ impl NakamotoConsensus {
             value, from, to
         ));
         let tx_id = node.spawn_transaction(from, to, value);
-        node.get::<NakamotoNodeState>()
-            .register_transaction_id(tx_id);
+        Self::state(node).register_transaction_id(tx_id);
         SimpleFlooding::flood(node, InventoryItem::Transaction(tx_id));
         Ok(())
     }
@@ -107,19 +471,27 @@ impl NakamotoConsensus {
         node: &mut NodeInterface,
         block_limit: Option<usize>,
     ) -> Result<(), Box<dyn Error>> {
-        let tip = node.get::<NakamotoNodeState>().tip;
-        let contents = node
-            .get::<NakamotoNodeState>()
-            .drain_unconfirmed_transactions(block_limit);
-        let block_header = node.spawn_block(tip, contents);
+        let state = Self::state(node);
+        let (tip, genesis, is_at_genesis) = (state.tip, state.genesis, state.is_at_genesis());
+        let contents = state.drain_unconfirmed_transactions(block_limit);
+        let block_header = if is_at_genesis {
+            node.spawn_first_block(genesis, contents)
+        } else {
+            node.spawn_block(Some(tip), contents)
+        };
         let block_contents = node.get_block_contents(block_header.id).unwrap().clone();
-        node.log(&format!(
-            "Mined a new block of height {} that contains {} transactions.",
-            block_header.height,
-            block_contents.len()
-        ));
-        node.get::<NakamotoNodeState>()
-            .register_block(block_header, block_contents);
+        let tx_count = block_contents.len();
+        Self::register_block(node, block_header, block_contents);
+        let tip_height = Self::state(node).tip_height();
+        node.log_at(
+            LogLevel::Normal,
+            &format!(
+                "Mined block {} at height {} with {} transactions.",
+                block_header.short_id(),
+                tip_height,
+                tx_count
+            ),
+        );
         SimpleFlooding::flood(node, InventoryItem::Block(block_header.id));
         Ok(())
     }
@@ -128,11 +500,15 @@ impl NakamotoConsensus {
         Ok(())
     }
     fn handle_peer_added(node: &mut NodeInterface, peer: Entity) -> Result<(), Box<dyn Error>> {
-        let all_blocks_sorted = node.get::<NakamotoNodeState>().known_blocks_sorted();
+        // everybody knows the genesis block
+        let all_blocks_sorted = Self::state(node).known_blocks_sorted();
         SimpleFlooding::<InventoryItem>::flood_peer_with(
             node,
             peer,
-            all_blocks_sorted.into_iter().map(InventoryItem::Block),
+            all_blocks_sorted
+                .into_iter()
+                .skip(1)
+                .map(InventoryItem::Block),
         );
         Ok(())
     }
@@ -143,6 +519,17 @@ pub enum InventoryItem {
     Transaction(Entity),
     Block(Entity),
 }
+impl Inventory for InventoryItem {
+    fn entity(&self) -> Entity {
+        match *self {
+            Self::Transaction(tx_id) => tx_id,
+            Self::Block(block_id) => block_id,
+        }
+    }
+    fn is_prominent(&self) -> bool {
+        matches!(self, Self::Block(_))
+    }
+}
 
 impl Protocol for NakamotoConsensus {
     type MessagePayload = SimpleFloodingMessage<InventoryItem>;
@@ -165,6 +552,20 @@ impl Protocol for NakamotoConsensus {
             .handle_message(node, underlay_message, message_payload)
     }
 
+    fn on_node_spawned(&self, mut node: NodeInterface) -> Result<(), Box<dyn Error>> {
+        if let Some(genesis) = self.genesis {
+            *Self::state(&mut node) = NakamotoNodeState::new_with_genesis(genesis);
+        }
+        Ok(())
+    }
+
+    fn node_status(&self, mut node: NodeInterface) -> String {
+        let (height, forks) = node.try_get::<NakamotoNodeState>().map_or((0, 0), |state| {
+            (state.tip_height(), state.fork_tips().len())
+        });
+        format!("h={} forks={}", height, forks)
+    }
+
     fn handle_poke(&self, mut node: NodeInterface) -> Result<(), Box<dyn Error>> {
         Self::handle_mining_success(&mut node, self.block_limit)
     }
@@ -186,35 +587,96 @@ impl Protocol for NakamotoConsensus {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+/// Relative hash rate of a node. Nodes without this component are treated as having a mining
+/// power of `1.`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MiningPower(pub f64);
+impl MiningPower {
+    /// The mining power of the node, or the default if it has none.
+    pub fn of(node: Entity, world: &World) -> f64 {
+        world
+            .get::<MiningPower>(node)
+            .map_or(Self::default().0, |power| power.0)
+    }
+}
+impl Default for MiningPower {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct NakamotoNodeState {
+    /// `None` for the default genesis block, like `BlockHeader::genesis`.
+    genesis: Option<Entity>,
+    /// The genesis block itself, see `genesis_block`.
+    genesis_block: Entity,
+    /// All known blocks, forks and the genesis block (at height 0) included.
     known_blocks: HashMap<Entity, BlockHeader>,
-    tip: Option<Entity>,
+    /// All known blocks (forks included) that contain a given transaction.
+    blocks_of_txes: HashMap<Entity, Vec<Entity>>,
+    tip: Entity,
     fork_tips: HashSet<Entity>,
     txes_unconfirmed: BTreeSet<Entity>,
     txes_confirmed: HashSet<Entity>,
+    /// Transactions that lost their confirmation because the block confirming them was orphaned.
+    reverted_txes: Vec<Entity>,
 }
 impl NakamotoNodeState {
+    /// A node on the simulation's default genesis block (see
+    /// `Simulation::default_genesis_block`), which is its tip until it learns about other blocks.
+    pub fn new(sim: &Simulation) -> Self {
+        Self::new_on(None, sim.default_genesis_block())
+    }
+    pub fn new_with_genesis(genesis: Entity) -> Self {
+        Self::new_on(Some(genesis), genesis)
+    }
+    fn new_on(genesis: Option<Entity>, genesis_block: Entity) -> Self {
+        let genesis_header = BlockHeader {
+            id: genesis_block,
+            id_prev: None,
+            genesis,
+            height: 0,
+            difficulty: 0,
+            chainwork: 0,
+            // nobody mined it
+            miner: genesis_block,
+            timestamp: OrderedFloat(0.),
+        };
+        Self {
+            genesis,
+            genesis_block,
+            known_blocks: HashMap::from([(genesis_block, genesis_header)]),
+            blocks_of_txes: HashMap::new(),
+            tip: genesis_block,
+            fork_tips: HashSet::new(),
+            txes_unconfirmed: BTreeSet::new(),
+            txes_confirmed: HashSet::new(),
+            reverted_txes: vec![],
+        }
+    }
     /// Returns `true` if we have updated the tip of the blockchain.
     fn register_block(&mut self, header: BlockHeader, contents: BlockContents) -> bool {
         // Our simple logic here assumes that blocks always arrive in the same order.
         // Making this better might be a TODO.
-        if self.known_blocks.contains_key(&header.id) {
+        let id_prev = header.id_prev.unwrap_or(self.genesis_block);
+        if self.known_blocks.contains_key(&header.id) || header.genesis != self.genesis {
             false
-        } else if header.id_prev == self.tip {
-            self.known_blocks.insert(header.id, header);
+        } else if id_prev == self.tip {
+            self.insert_known_block(header, &contents);
             self.register_new_tip(header.id, contents);
             true
-        } else if header.id_prev == None {
-            self.known_blocks.insert(header.id, header);
-            self.fork_tips.insert(header.id);
-            false
-        } else if self.known_blocks.contains_key(&header.id_prev.unwrap()) {
-            self.known_blocks.insert(header.id, header);
-            self.fork_tips.remove(&header.id_prev.unwrap()); // will do nothing if it's a new fork
+        } else if self.known_blocks.contains_key(&id_prev) {
+            self.insert_known_block(header, &contents);
+            self.fork_tips.remove(&id_prev); // will do nothing if it's a new fork
             self.fork_tips.insert(header.id);
-            if header.height > self.tip_height() {
-                let old_tip = self.tip.unwrap();
+            // the chain with the most work wins, which isn't necessarily the longest one; among
+            // equally heavy chains, the tip with the lowest ID wins, so the outcome doesn't depend
+            // on the order blocks arrive in
+            let old_tip = self.tip;
+            if (header.chainwork, std::cmp::Reverse(header.id))
+                > (self.tip_chainwork(), std::cmp::Reverse(old_tip))
+            {
                 self.register_new_tip(header.id, contents);
                 self.fork_tips.remove(&header.id);
                 self.fork_tips.insert(old_tip);
@@ -226,8 +688,17 @@ impl NakamotoNodeState {
             false
         }
     }
+    fn insert_known_block(&mut self, header: BlockHeader, contents: &BlockContents) {
+        self.known_blocks.insert(header.id, header);
+        for &tx_id in contents.iter() {
+            self.blocks_of_txes
+                .entry(tx_id)
+                .or_default()
+                .push(header.id);
+        }
+    }
     fn register_new_tip(&mut self, block_id: Entity, block_contents: BlockContents) {
-        self.tip = Some(block_id);
+        self.tip = block_id;
         for tx_id in block_contents.into_iter() {
             self.txes_unconfirmed.remove(&tx_id);
             self.txes_confirmed.insert(tx_id);
@@ -256,27 +727,228 @@ impl NakamotoNodeState {
     pub fn block_header(&self, block_id: Entity) -> Option<BlockHeader> {
         self.known_blocks.get(&block_id).copied()
     }
-    pub fn tip(&self) -> Option<Entity> {
+    /// The block a known block builds upon, i.e., the genesis block for the first blocks of the
+    /// chain. `None` for the genesis block itself and for unknown blocks.
+    pub fn previous_block(&self, block_id: Entity) -> Option<Entity> {
+        let header = self.block_header(block_id)?;
+        (block_id != self.genesis_block).then(|| header.id_prev.unwrap_or(self.genesis_block))
+    }
+    /// The genesis block until the node learns about other blocks.
+    pub fn tip(&self) -> Entity {
         self.tip
     }
+    pub fn tip_header(&self) -> BlockHeader {
+        self.known_blocks[&self.tip]
+    }
+    /// Whether the node hasn't learned about any block building upon the genesis block yet.
+    pub fn is_at_genesis(&self) -> bool {
+        self.tip == self.genesis_block
+    }
+    /// `None` for the default genesis block, like `BlockHeader::genesis`. See `genesis_block`
+    /// for the genesis block itself.
+    pub fn genesis(&self) -> Option<Entity> {
+        self.genesis
+    }
+    /// The genesis block at the start of the chain, i.e., `Simulation::default_genesis_block` or
+    /// the one the node was configured with (see `UseGenesisBlock`).
+    pub fn genesis_block(&self) -> Entity {
+        self.genesis_block
+    }
     pub fn fork_tips(&self) -> &HashSet<Entity> {
         &self.fork_tips
     }
-    pub fn height(&self, block_id: Option<Entity>) -> usize {
-        if let Some(block_id) = block_id {
-            self.known_blocks
-                .get(&block_id)
-                .expect("Requested height of unknown block.")
-                .height
-        } else {
-            0
-        }
+    /// The number of blocks the node knows about, forks included, but not the genesis block.
+    pub fn known_block_count(&self) -> usize {
+        self.known_blocks.len() - 1
+    }
+    /// The tip and all fork tips with their heights, highest first (the tip first among equally
+    /// high ones).
+    pub fn all_tips(&self) -> Vec<(Entity, usize)> {
+        let mut all_tips: Vec<(Entity, usize)> = std::iter::once(&self.tip)
+            .chain(self.fork_tips.iter())
+            .map(|&block_id| (block_id, self.height(block_id)))
+            .collect();
+        // stable, so the tip stays in front
+        all_tips[1..].sort_unstable_by_key(|&(block_id, _)| block_id);
+        all_tips.sort_by_key(|&(_, height)| std::cmp::Reverse(height));
+        all_tips
+    }
+    /// How many blocks the given (fork tip) block lags behind the tip.
+    pub fn fork_depth(&self, block_id: Entity) -> usize {
+        self.tip_height().saturating_sub(self.height(block_id))
+    }
+    /// The depths (see `fork_depth`) of all fork tips, smallest first.
+    pub fn fork_depths(&self) -> Vec<usize> {
+        let mut fork_depths: Vec<usize> = self
+            .fork_tips
+            .iter()
+            .map(|&fork_tip| self.fork_depth(fork_tip))
+            .collect();
+        fork_depths.sort_unstable();
+        fork_depths
+    }
+    pub fn height(&self, block_id: Entity) -> usize {
+        self.known_blocks
+            .get(&block_id)
+            .expect("Requested height of unknown block.")
+            .height
     }
     pub fn tip_height(&self) -> usize {
         self.height(self.tip)
     }
+    /// See `BlockHeader::chainwork`.
+    pub fn tip_chainwork(&self) -> u64 {
+        self.tip_header().chainwork
+    }
+    /// Returns the ids of all blocks in the main chain, starting with the genesis block and
+    /// ending with the tip, so there are `tip_height() + 1` blocks.
+    pub fn main_chain(&self) -> Vec<Entity> {
+        let mut main_chain = Vec::with_capacity(self.tip_height() + 1);
+        let mut block_id = Some(self.tip);
+        while let Some(id) = block_id {
+            main_chain.push(id);
+            block_id = self.previous_block(id);
+        }
+        main_chain.reverse();
+        main_chain
+    }
+    /// Like `main_chain`, but fails instead of panicking if the chain is broken, i.e., if it
+    /// contains unknown blocks or heights that aren't consecutive.
+    fn checked_main_chain(&self) -> Result<Vec<Entity>, String> {
+        let mut main_chain = vec![];
+        let mut expected_height = self.tip_height();
+        let mut block_id = Some(self.tip);
+        while let Some(id) = block_id {
+            let header = self.block_header(id).ok_or_else(|| {
+                format!(
+                    "unknown block {} at height {}",
+                    short_hash(id),
+                    expected_height
+                )
+            })?;
+            if header.height != expected_height {
+                return Err(format!(
+                    "block {} has height {} instead of {}",
+                    short_hash(id),
+                    header.height,
+                    expected_height
+                ));
+            }
+            main_chain.push(id);
+            if id == self.genesis_block {
+                break;
+            } else if expected_height == 0 {
+                return Err(format!(
+                    "chain ends at block {} instead of the genesis block",
+                    short_hash(id)
+                ));
+            }
+            block_id = self.previous_block(id);
+            expected_height -= 1;
+        }
+        main_chain.reverse();
+        Ok(main_chain)
+    }
+    pub fn is_on_main_chain(&self, block_id: Entity) -> bool {
+        let height = match self.block_header(block_id) {
+            Some(header) => header.height,
+            None => return false,
+        };
+        let mut main_chain_block = Some(self.tip);
+        while let Some(header) = main_chain_block.and_then(|id| self.block_header(id)) {
+            if header.height <= height {
+                return header.id == block_id;
+            }
+            main_chain_block = self.previous_block(header.id);
+        }
+        false
+    }
+    /// The number of main chain blocks built on top of the block containing the transaction.
+    /// `None` if the transaction is not in the main chain (i.e., unknown or only in forks).
+    pub fn confirmations(&self, tx_id: Entity) -> Option<usize> {
+        let block_id = self.block_of_tx(tx_id)?;
+        self.is_on_main_chain(block_id)
+            .then(|| self.tip_height() - self.height(block_id))
+    }
+    /// The known block that contains the transaction, preferring the main chain over forks.
+    pub fn block_of_tx(&self, tx_id: Entity) -> Option<Entity> {
+        let blocks = self.blocks_of_txes.get(&tx_id)?;
+        blocks
+            .iter()
+            .find(|&&block_id| self.is_on_main_chain(block_id))
+            .or_else(|| blocks.first())
+            .copied()
+    }
+    /// Returns the most recent block that both blocks build upon (which might be one of the blocks
+    /// themselves), at least the genesis block. Returns `None` if one of them is unknown.
+    pub fn common_ancestor(&self, block_a: Entity, block_b: Entity) -> Option<Entity> {
+        let mut a = self.block_header(block_a)?;
+        let mut b = self.block_header(block_b)?;
+        while a.height > b.height {
+            a = self.block_header(self.previous_block(a.id)?)?;
+        }
+        while b.height > a.height {
+            b = self.block_header(self.previous_block(b.id)?)?;
+        }
+        while a.id != b.id {
+            a = self.block_header(self.previous_block(a.id)?)?;
+            b = self.block_header(self.previous_block(b.id)?)?;
+        }
+        Some(a.id)
+    }
+    /// The blocks that would be orphaned if the main chain wins over the fork ending in
+    /// `fork_tip`: from `fork_tip` back to (but excluding) its common ancestor with the tip, most
+    /// recent first. Empty for blocks on the main chain and unknown blocks.
+    pub fn fork_blocks(&self, fork_tip: Entity) -> Vec<Entity> {
+        let ancestor = self.common_ancestor(self.tip, fork_tip);
+        let mut fork_blocks = vec![];
+        let mut block = Some(fork_tip).filter(|_| ancestor.is_some());
+        while let Some(id) = block.filter(|&id| Some(id) != ancestor) {
+            fork_blocks.push(id);
+            block = self.previous_block(id);
+        }
+        fork_blocks
+    }
+    /// The fraction of the last `window` blocks in the main chain that were mined by `node`.
+    pub fn chain_quality(&self, node: Entity, window: usize) -> f64 {
+        let mut n_blocks = 0;
+        let mut n_blocks_by_node = 0;
+        let mut block_id = self.tip;
+        while block_id != self.genesis_block && n_blocks < window {
+            n_blocks += 1;
+            if self.known_blocks[&block_id].miner == node {
+                n_blocks_by_node += 1;
+            }
+            block_id = self.previous_block(block_id).unwrap();
+        }
+        if n_blocks == 0 {
+            0.
+        } else {
+            n_blocks_by_node as f64 / n_blocks as f64
+        }
+    }
+    /// The average number of main chain blocks per simulated second, measured from the start of
+    /// the simulation until the current tip was mined.
+    pub fn chain_growth_rate(&self) -> f64 {
+        let header = self.tip_header();
+        if header.timestamp > OrderedFloat(0.) {
+            header.height as f64 / header.timestamp.into_inner()
+        } else {
+            0.
+        }
+    }
+    /// The average time between consecutive main chain blocks, based on their timestamps. `None`
+    /// if there are fewer than two blocks (not counting the genesis block).
+    pub fn average_block_interval(&self) -> Option<f64> {
+        let main_chain = &self.main_chain()[1..];
+        let first = self.block_header(*main_chain.first()?)?;
+        let last = self.block_header(*main_chain.last()?)?;
+        (main_chain.len() >= 2).then(|| {
+            (last.timestamp - first.timestamp).into_inner() / (main_chain.len() - 1) as f64
+        })
+    }
     /// Returns the ids of all known blocks (forks included) sorted by their block height,
-    /// smallest heights first.
+    /// smallest heights first, i.e., starting with the genesis block.
     pub fn known_blocks_sorted(&self) -> Vec<Entity> {
         let mut blocks_heights: Vec<(usize, Entity)> = self
             .known_blocks
@@ -289,6 +961,175 @@ impl NakamotoNodeState {
     pub fn txes_unconfirmed(&self) -> &BTreeSet<Entity> {
         &self.txes_unconfirmed
     }
+    /// Transactions that were confirmed on the main chain until a reorg orphaned their block, in
+    /// the order they were detected.
+    pub fn reverted_txes(&self) -> &[Entity] {
+        &self.reverted_txes
+    }
+    pub fn reverted_tx_count(&self) -> usize {
+        self.reverted_txes.len()
+    }
+    /// Returns a Graphviz DOT description of all known blocks, pointing towards the genesis
+    /// block. Blocks in the main chain are drawn bold, blocks in forks dashed.
+    pub fn to_dot(&self) -> String {
+        let main_chain: HashSet<Entity> = self.main_chain().into_iter().collect();
+        let dot_id = |block_id: Entity| {
+            if block_id == self.genesis_block {
+                "genesis".to_string()
+            } else {
+                format!("b{}", block_id.id())
+            }
+        };
+
+        let mut dot = String::from("digraph blockchain {\n");
+        dot.push_str("    rankdir=RL;\n");
+        dot.push_str("    genesis [label=\"Genesis\", shape=box, style=dotted];\n");
+        for block_id in self.known_blocks_sorted().into_iter().skip(1) {
+            let header = self.known_blocks[&block_id];
+            let style = if main_chain.contains(&block_id) {
+                "bold"
+            } else {
+                "dashed"
+            };
+            let prev = dot_id(self.previous_block(block_id).unwrap());
+            dot.push_str(&format!(
+                "    b{} [label=\"{} ({})\", shape=box, style={}];\n",
+                block_id.id(),
+                header.short_id(),
+                header.height,
+                style
+            ));
+            dot.push_str(&format!("    b{} -> {};\n", block_id.id(), prev));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Records the tip height of every node at most once per `interval`, e.g., for plotting how fast
+/// the network converges. Samples are taken while handling events; `sample_if_due` can also be
+/// called directly from a render or step loop.
+#[derive(Debug, Clone)]
+pub struct TipHeightSampler {
+    interval: SimSeconds,
+    next_sample_at: SimSeconds,
+    samples: Vec<(SimSeconds, Entity, usize)>,
+}
+impl TipHeightSampler {
+    pub fn new(interval: SimSeconds) -> Self {
+        Self {
+            interval,
+            next_sample_at: OrderedFloat(0.),
+            samples: vec![],
+        }
+    }
+    /// Returns `true` if a sample was taken.
+    pub fn sample_if_due(&mut self, sim: &Simulation) -> bool {
+        let now = sim.time.now();
+        if now < self.next_sample_at {
+            false
+        } else {
+            self.sample(sim);
+            self.next_sample_at = now + self.interval;
+            true
+        }
+    }
+    pub fn sample(&mut self, sim: &Simulation) {
+        let now = sim.time.now();
+        let mut new_samples: Vec<(SimSeconds, Entity, usize)> = sim
+            .world
+            .query::<&NakamotoNodeState>()
+            .iter()
+            .map(|(node, state)| (now, node, state.tip_height()))
+            .collect();
+        new_samples.sort_by_key(|&(_, node, _)| node);
+        self.samples.extend(new_samples);
+    }
+    /// All samples as `(sim_time, node, tip_height)`, oldest first.
+    pub fn samples(&self) -> &[(SimSeconds, Entity, usize)] {
+        &self.samples
+    }
+    pub fn export_csv(&self) -> String {
+        let mut csv = String::from("sim_time,node,tip_height\n");
+        for (time, node, tip_height) in self.samples.iter() {
+            csv.push_str(&format!("{},{},{}\n", time, node.id(), tip_height));
+        }
+        csv
+    }
+}
+impl EventHandler for TipHeightSampler {
+    fn handle_event(
+        &mut self,
+        sim: &mut Simulation,
+        _: simulation::Event,
+    ) -> Result<(), Box<dyn Error>> {
+        self.sample_if_due(sim);
+        Ok(())
+    }
+    fn reset(&mut self, _: &mut Simulation) {
+        self.next_sample_at = OrderedFloat(0.);
+        self.samples.clear();
+    }
+}
+
+/// Breakpoints for studying rare events: pauses the clock (and logs why) as soon as a node
+/// registers a new fork (`pause_on_fork`) or switches to another fork (`pause_on_reorg`).
+#[derive(Debug, Clone, Default)]
+pub struct PauseOnForks {
+    pub pause_on_fork: bool,
+    pub pause_on_reorg: bool,
+    /// The tip and the number of fork tips of each node, as of its last event.
+    last_seen: HashMap<Entity, (Entity, usize)>,
+}
+impl PauseOnForks {
+    pub fn new(pause_on_fork: bool, pause_on_reorg: bool) -> Self {
+        Self {
+            pause_on_fork,
+            pause_on_reorg,
+            last_seen: HashMap::new(),
+        }
+    }
+}
+impl EventHandler for PauseOnForks {
+    fn handle_event(
+        &mut self,
+        sim: &mut Simulation,
+        event: simulation::Event,
+    ) -> Result<(), Box<dyn Error>> {
+        let node = match event {
+            simulation::Event::Node(node, _) => node,
+            _ => return Ok(()),
+        };
+        let (tip, fork_count, is_reorg) = match sim.world.get::<NakamotoNodeState>(node) {
+            Ok(state) => {
+                let (tip, fork_count) = (state.tip(), state.fork_tips().len());
+                let is_reorg = match self.last_seen.get(&node) {
+                    Some(&(old_tip, _)) => state.common_ancestor(old_tip, tip) != Some(old_tip),
+                    None => false,
+                };
+                (tip, fork_count, is_reorg)
+            }
+            Err(_) => return Ok(()),
+        };
+        let old_fork_count = self
+            .last_seen
+            .insert(node, (tip, fork_count))
+            .map_or(0, |(_, old_fork_count)| old_fork_count);
+        if self.pause_on_reorg && is_reorg {
+            sim.time.pause();
+            sim.log(format!(
+                "Paused: {} switched to another fork.",
+                sim.name(node)
+            ));
+        } else if self.pause_on_fork && fork_count > old_fork_count {
+            sim.time.pause();
+            sim.log(format!("Paused: {} registered a new fork.", sim.name(node)));
+        }
+        Ok(())
+    }
+    fn reset(&mut self, _: &mut Simulation) {
+        self.last_seen.clear();
+    }
 }
 
 #[cfg(test)]
@@ -322,50 +1163,326 @@ mod tests {
         sim.add_peer(node3, node2);
 
         sim.do_now(ForSpecific(node1, MineBlock));
-        sim.catch_up(100.);
+        assert!(sim.work_until_converged(OrderedFloat(100.)).is_some());
 
         let state1 = get_state(&sim, node1);
         let state2 = get_state(&sim, node2);
         let state3 = get_state(&sim, node3);
 
+        assert!(!state1.is_at_genesis());
         assert_eq!(state1.tip, state2.tip);
         assert_eq!(state1.tip, state3.tip);
     }
 
     #[wasm_bindgen_test]
-    fn transactions_get_distributed() {
+    fn unconnected_nodes_do_not_converge() {
         let mut sim = Simulation::new();
         sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
 
         let node1 = sim.spawn_random_node();
-        let node2 = sim.spawn_random_node();
-        let node3 = sim.spawn_random_node();
-        sim.add_peer(node1, node2);
-        sim.add_peer(node2, node1);
-        sim.add_peer(node2, node3);
-        sim.add_peer(node3, node2);
-
-        sim.do_now(ForSpecific(
-            node1,
-            BuildAndBroadcastTransaction::from("Alice", "Bob", 32),
-        ));
-        sim.catch_up(100.);
-
-        let state1 = get_state(&sim, node1);
-        let state2 = get_state(&sim, node2);
-        let state3 = get_state(&sim, node3);
+        sim.spawn_random_node();
+        assert!(sim.is_converged());
 
-        assert!(!state1.txes_unconfirmed.is_empty());
-        assert_eq!(state1.txes_unconfirmed, state2.txes_unconfirmed);
-        assert_eq!(state1.txes_unconfirmed, state3.txes_unconfirmed);
+        sim.do_now(ForSpecific(node1, MineBlock));
+        assert_eq!(None, sim.work_until_converged(OrderedFloat(10.)));
+        assert!(!sim.is_converged());
     }
 
     #[wasm_bindgen_test]
-    fn transactions_end_up_in_blocks() {
+    fn state_table_of_converged_network_shows_same_tip() {
         let mut sim = Simulation::new();
         sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        sim.do_now(SpawnRandomNodes(10));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+        let miner = sim.pick_random_node().unwrap();
+        sim.do_now(ForSpecific(miner, MineBlock));
+        sim.do_now(ForSpecific(miner, MineBlock));
+        assert!(sim.work_until_converged(OrderedFloat(100.)).is_some());
+
+        let table = sim.state_table();
+        assert_eq!(10, table.len());
+        let tip_short_id = Some(short_hash(get_state(&sim, miner).tip()));
+        for summary in table {
+            assert_eq!(tip_short_id, summary.tip_short_id);
+            assert_eq!(2, summary.tip_height);
+            assert_eq!(0, summary.fork_count);
+            assert!(summary.peer_count > 0);
+        }
+    }
 
-        let node1 = sim.spawn_random_node();
+    #[wasm_bindgen_test]
+    fn simulation_pauses_at_first_fork() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        sim.add_event_handler(PauseOnForks::new(true, false));
+        let node1 = sim.spawn_random_node_at_position(0., 0.);
+        let node2 = sim.spawn_random_node_at_position(800., 0.);
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+
+        // node2 gets node1's block first
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_in(OrderedFloat(0.01), ForSpecific(node2, MineBlock));
+        sim.catch_up(100.);
+
+        assert!(sim.time.paused());
+        let paused_at = sim.time.now();
+        assert!(paused_at > OrderedFloat(0.01) && paused_at < OrderedFloat(10.));
+        assert!(get_state(&sim, node1).fork_tips().is_empty());
+        assert_eq!(1, get_state(&sim, node2).fork_tips().len());
+        assert!(sim
+            .logger
+            .entries()
+            .any(|entry| entry.message.ends_with("registered a new fork.")));
+
+        sim.catch_up(100.);
+        assert_eq!(paused_at, sim.time.now());
+    }
+
+    #[wasm_bindgen_test]
+    fn tip_agreement_reflects_split() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        let nodes: Vec<Entity> = (0..5).map(|_| sim.spawn_random_node()).collect();
+        for (node1, node2) in [(nodes[0], nodes[1]), (nodes[2], nodes[3])] {
+            sim.add_peer(node1, node2);
+            sim.add_peer(node2, node1);
+        }
+        sim.do_now(ForSpecific(nodes[0], MineBlock));
+        sim.do_now(ForSpecific(nodes[2], MineBlock));
+        sim.catch_up(100.);
+
+        let agreement = sim.tip_agreement();
+        assert_eq!(5, agreement.values().sum::<usize>());
+        assert_eq!(Some(&2), agreement.get(&get_state(&sim, nodes[0]).tip()));
+        assert_eq!(Some(&2), agreement.get(&get_state(&sim, nodes[2]).tip()));
+        assert_eq!(Some(&1), agreement.get(&sim.default_genesis_block()));
+    }
+
+    #[wasm_bindgen_test]
+    fn mining_is_logged_unless_quiet() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        let node = sim.spawn_random_node();
+
+        sim.do_now(ForSpecific(node, MineBlock));
+        sim.catch_up(1.);
+        let tip = get_state(&sim, node).tip_header();
+        let expected = format!(
+            "Mined block {} at height 1 with 0 transactions.",
+            tip.short_id()
+        );
+        assert_eq!(expected, sim.logger.entries().next().unwrap().message);
+
+        sim.set_log_level(LogLevel::Quiet);
+        sim.do_now(ForSpecific(node, MineBlock));
+        sim.catch_up(1.);
+        assert_eq!(2, get_state(&sim, node).tip_height());
+        assert_eq!(expected, sim.logger.entries().next().unwrap().message);
+    }
+
+    #[wasm_bindgen_test]
+    fn set_mining_power_changes_miner_selection() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        sim.do_now(SetMiningPower(node1, 0.));
+        sim.catch_up(1.);
+        assert_eq!(0., MiningPower::of(node1, &sim.world));
+        assert_eq!(1., MiningPower::of(node2, &sim.world));
+        for _ in 0..20 {
+            assert_eq!(Some(node2), sim.pick_random_node_weighted(MiningPower::of));
+        }
+
+        sim.do_now(SetMiningPower(node1, 3.));
+        sim.do_now(SetMiningPower(node2, 0.));
+        sim.do_now(SetMiningPower(node2, -1.));
+        sim.catch_up(1.);
+        assert_eq!(0., MiningPower::of(node2, &sim.world));
+        for _ in 0..20 {
+            assert_eq!(Some(node1), sim.pick_random_node_weighted(MiningPower::of));
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn stepping_to_next_block_mines_exactly_one_block() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        sim.do_now(SpawnRandomNodes(5));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+        let block_count = |sim: &Simulation| sim.world.query::<&BlockHeader>().iter().count();
+        assert_eq!(None, sim.step_to_next_block(OrderedFloat(100.)));
+
+        sim.do_now(StartMining { rate: 0.1 });
+        for expected in 1..=3 {
+            assert!(sim.step_to_next_block(OrderedFloat(1000.)).is_some());
+            assert_eq!(expected, block_count(&sim));
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn start_mining_produces_blocks_at_the_given_rate() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        // unconnected nodes, so each tip height counts the blocks mined at that node
+        let miner1 = sim.spawn_random_node();
+        let miner2 = sim.spawn_random_node();
+        let idle = sim.spawn_random_node();
+        sim.node_interface(idle).get::<MiningPower>().0 = 0.;
+
+        let rate = 0.5;
+        let window = 200.;
+        sim.do_now(StartMining { rate });
+        sim.work_until(OrderedFloat(window));
+        let blocks_mined = |sim: &Simulation| {
+            [miner1, miner2]
+                .iter()
+                .map(|&miner| {
+                    sim.world
+                        .get::<NakamotoNodeState>(miner)
+                        .map_or(0, |state| state.tip_height())
+                })
+                .sum::<usize>()
+        };
+        let expected = rate * window;
+        let produced = blocks_mined(&sim) as f64;
+        // about four standard deviations of the Poisson distribution
+        assert!((produced - expected).abs() < 4. * expected.sqrt());
+        assert!(sim.world.get::<NakamotoNodeState>(idle).is_err());
+
+        sim.do_now(StopMining);
+        sim.work_until(OrderedFloat(window + 1.));
+        let produced = blocks_mined(&sim);
+        sim.work_until(OrderedFloat(2. * window));
+        assert_eq!(produced, blocks_mined(&sim));
+    }
+
+    #[wasm_bindgen_test]
+    fn processing_delay_postpones_tip_update() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.do_now(SetProcessingDelay(node2, OrderedFloat(5.)));
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.work_until(sim.time.now());
+        let arrival_time = sim
+            .world
+            .query::<(&UnderlayMessage, &TimeSpan)>()
+            .iter()
+            .find(|(_, (message, _))| message.dest == node2)
+            .map(|(_, (_, time_span))| time_span.end)
+            .unwrap();
+
+        sim.work_until(arrival_time);
+        assert!(sim.world.get::<NakamotoNodeState>(node2).is_err());
+        sim.work_until(arrival_time + OrderedFloat(4.9));
+        assert!(sim.world.get::<NakamotoNodeState>(node2).is_err());
+        sim.work_until(arrival_time + OrderedFloat(5.));
+        assert_eq!(1, get_state(&sim, node2).tip_height());
+    }
+
+    #[wasm_bindgen_test]
+    fn pokes_do_nothing_after_removing_protocol() {
+        let mut sim = Simulation::new();
+        let protocol =
+            sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        let node = sim.spawn_random_node();
+
+        sim.do_now(PokeSpecificNode(node));
+        sim.catch_up(1.);
+        assert_eq!(1, get_state(&sim, node).tip_height());
+
+        assert!(sim.remove_event_handler(protocol));
+        sim.do_now(PokeSpecificNode(node));
+        sim.catch_up(1.);
+        assert_eq!(1, get_state(&sim, node).tip_height());
+    }
+
+    #[wasm_bindgen_test]
+    fn joining_node_catches_up_with_network() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        sim.do_now(SpawnRandomNodes(10));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+        let nodes = sim.all_nodes();
+        for &node in nodes.iter().take(3) {
+            sim.do_now(ForSpecific(node, MineBlock));
+            sim.catch_up(100.);
+        }
+
+        let newcomer = sim.spawn_random_node();
+        sim.do_now(JoinNetwork {
+            node: newcomer,
+            target_degree: 3,
+            prefer_well_connected: true,
+        });
+        sim.catch_up(100.);
+
+        assert_eq!(3, sim.peers_mut(newcomer).len());
+        assert_eq!(
+            get_state(&sim, nodes[0]).tip(),
+            get_state(&sim, newcomer).tip()
+        );
+        assert!(sim.is_converged());
+    }
+
+    #[wasm_bindgen_test]
+    fn mine_block_at_node_mines_at_that_node() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        sim.do_now(MineBlockAtNode(node2));
+        sim.catch_up(100.);
+
+        assert_eq!(1, get_state(&sim, node2).tip_height());
+        assert!(sim.world.get::<NakamotoNodeState>(node1).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn transactions_get_distributed() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        let node3 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        sim.add_peer(node2, node3);
+        sim.add_peer(node3, node2);
+
+        sim.do_now(ForSpecific(
+            node1,
+            BuildAndBroadcastTransaction::from("Alice", "Bob", 32),
+        ));
+        sim.catch_up(100.);
+
+        let state1 = get_state(&sim, node1);
+        let state2 = get_state(&sim, node2);
+        let state3 = get_state(&sim, node3);
+
+        assert!(!state1.txes_unconfirmed.is_empty());
+        assert_eq!(state1.txes_unconfirmed, state2.txes_unconfirmed);
+        assert_eq!(state1.txes_unconfirmed, state3.txes_unconfirmed);
+    }
+
+    #[wasm_bindgen_test]
+    fn transactions_end_up_in_blocks() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
         let node2 = sim.spawn_random_node();
         sim.add_peer(node1, node2);
         sim.add_peer(node2, node1);
@@ -386,7 +1503,7 @@ mod tests {
         assert!(state2.txes_unconfirmed.is_empty());
         assert!(!state1.txes_confirmed.is_empty());
 
-        let block_id = state1.tip().unwrap();
+        let block_id = state1.tip();
         let block_contents = sim
             .world
             .query_one::<&BlockContents>(block_id)
@@ -429,7 +1546,7 @@ mod tests {
         assert_eq!(1, state.txes_unconfirmed.len());
         assert_eq!(2, state.txes_confirmed.len());
 
-        let block_id = state.tip().unwrap();
+        let block_id = state.tip();
         let block_contents = sim
             .world
             .query_one::<&BlockContents>(block_id)
@@ -493,14 +1610,40 @@ mod tests {
         let state1 = get_state(&sim, node1);
         let state3 = get_state(&sim, node3);
 
-        assert_ne!(state1.tip, state3.tip);
+        // ties are broken the same way on all nodes
+        assert_eq!(state1.tip, state3.tip);
 
         let fork_tip_1 = state1
             .fork_tips
             .into_iter()
             .next()
             .expect("No forks registered?!");
-        assert_eq!(fork_tip_1, state3.tip.unwrap());
+        assert_ne!(fork_tip_1, state1.tip);
+        assert_eq!(state3.fork_tips, HashSet::from([fork_tip_1]));
+    }
+
+    #[wasm_bindgen_test]
+    fn equally_heavy_forks_are_resolved_independently_of_order() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let mut node_interface = sim.node_interface(node);
+        let block1 = node_interface.spawn_block(None, []);
+        let block2a = node_interface.spawn_block(Some(block1.id), []);
+        let block2b = node_interface.spawn_block(Some(block1.id), []);
+
+        let mut state_a = NakamotoNodeState::new(&sim);
+        let mut state_b = NakamotoNodeState::new(&sim);
+        for block in [block1, block2a, block2b] {
+            state_a.register_block(block, BlockContents::new());
+        }
+        for block in [block1, block2b, block2a] {
+            state_b.register_block(block, BlockContents::new());
+        }
+
+        let expected = block2a.id.min(block2b.id);
+        assert_eq!(expected, state_a.tip());
+        assert_eq!(expected, state_b.tip());
+        assert_eq!(state_a.fork_tips(), state_b.fork_tips());
     }
 
     #[wasm_bindgen_test]
@@ -524,7 +1667,7 @@ mod tests {
         sim.catch_up(100.);
 
         sim.do_now(ForSpecific(node1, MineBlock));
-        sim.catch_up(100.);
+        assert!(sim.work_until_converged(OrderedFloat(100.)).is_some());
 
         let state1 = get_state(&sim, node1);
         let state3 = get_state(&sim, node3);
@@ -552,7 +1695,7 @@ mod tests {
 
         let mut remaining_blocks = state.known_blocks.clone();
 
-        let mut queue = vec![state.tip];
+        let mut queue = vec![Some(state.tip)];
         queue.extend(state.fork_tips.clone().into_iter().map(Some));
 
         while !queue.is_empty() {
@@ -562,7 +1705,7 @@ mod tests {
                     panic!("Block not connected to genesis hash!");
                 }
                 if let Entry::Occupied(block_entry) = remaining_blocks.entry(block_id) {
-                    queue.push(block_entry.get().id_prev);
+                    queue.push(state.previous_block(block_id));
                     block_entry.remove();
                 }
             }
@@ -592,10 +1735,782 @@ mod tests {
 
         sim.catch_up(10.);
 
+        assert_eq!(Ok(()), sim.consensus_check());
+    }
+
+    #[wasm_bindgen_test]
+    fn consensus_check_reports_diverging_and_broken_chains() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        assert_eq!(Ok(()), sim.consensus_check());
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(10.);
+        assert_eq!(Ok(()), sim.consensus_check());
+
+        sim.remove_peer(node1, node2);
+        sim.remove_peer(node2, node1);
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(10.);
+        let expected = format!(
+            "{}: diverges from {} at height 2",
+            sim.name(node2),
+            sim.name(node1)
+        );
+        assert_eq!(Err(expected), sim.consensus_check());
+
+        let mut state = get_state(&sim, node2);
+        let first_block = state.main_chain()[1];
+        state.known_blocks.remove(&first_block);
+        let error = state.checked_main_chain().unwrap_err();
+        assert!(error.starts_with("unknown block"));
+    }
+
+    #[wasm_bindgen_test]
+    fn nodes_on_different_genesis_blocks_never_converge() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        sim.do_now(SpawnRandomNodes(8));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+
+        let genesis = sim.spawn_genesis_block();
+        let nodes = sim.all_nodes();
+        let (group1, group2) = nodes.split_at(4);
+        for &node in group2 {
+            sim.do_now(ForSpecific(node, UseGenesisBlock(genesis)));
+        }
+        sim.catch_up(1.);
+
+        for i in 0..10 {
+            sim.do_now(ForSpecific(nodes[i % nodes.len()], MineBlock));
+            sim.catch_up(100.);
+        }
+        assert!(!sim.is_converged());
+
+        for &node in group1 {
+            let state = get_state(&sim, node);
+            let tip = state.block_header(state.tip).unwrap();
+            assert_eq!(None, tip.genesis);
+        }
+        for &node in group2 {
+            let state = get_state(&sim, node);
+            let tip = state.block_header(state.tip).unwrap();
+            assert_eq!(Some(genesis), tip.genesis);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn spawned_nodes_know_configured_genesis() {
+        let mut sim = Simulation::new();
+        let genesis = sim.spawn_genesis_block();
+        sim.add_event_handler(InvokeProtocolForAllNodes(
+            NakamotoConsensus::new_with_genesis(genesis),
+        ));
+
+        let node = sim.spawn_random_node();
+        assert_eq!(Some(genesis), get_state(&sim, node).genesis());
+
+        sim.do_now(ForSpecific(node, MineBlock));
+        sim.catch_up(1.);
+        let state = get_state(&sim, node);
+        assert_eq!(Some(genesis), state.tip_header().genesis);
+    }
+
+    fn spawn_and_register_block(
+        sim: &mut Simulation,
+        state: &mut NakamotoNodeState,
+        node: Entity,
+        id_prev: Option<Entity>,
+        txes: Vec<Entity>,
+    ) -> Entity {
+        let header = sim.node_interface(node).spawn_block(id_prev, txes.clone());
+        state.register_block(header, txes.into_iter().collect());
+        header.id
+    }
+
+    #[wasm_bindgen_test]
+    fn confirmations_count_blocks_on_top() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let tx =
+            sim.node_interface(node)
+                .spawn_transaction("Alice".to_string(), "Bob".to_string(), 42);
+        let mut state = NakamotoNodeState::new(&sim);
+
+        let mut block = spawn_and_register_block(&mut sim, &mut state, node, None, vec![tx]);
+        assert_eq!(Some(0), state.confirmations(tx));
+        for _ in 0..3 {
+            block = spawn_and_register_block(&mut sim, &mut state, node, Some(block), vec![]);
+        }
+
+        assert_eq!(Some(3), state.confirmations(tx));
+        assert_eq!(None, state.confirmations(block)); // not a transaction
+    }
+
+    /// The `(node, transaction)` pairs of all `TransactionReverted` events.
+    #[derive(Debug, Default)]
+    struct RevertedTxRecorder(Vec<(Entity, Entity)>);
+    impl EventHandler for RevertedTxRecorder {
+        fn handle_event(
+            &mut self,
+            _: &mut Simulation,
+            event: simulation::Event,
+        ) -> Result<(), Box<dyn Error>> {
+            if let simulation::Event::Node(node, NodeEvent::TransactionReverted(tx_id)) = event {
+                self.0.push((node, tx_id));
+            }
+            Ok(())
+        }
+    }
+
+    fn reverted_tx_events(sim: &Simulation, recorder: usize) -> Vec<(Entity, Entity)> {
+        let handlers = sim.additional_event_handlers();
+        let handlers = handlers.borrow();
+        handlers
+            .get::<RevertedTxRecorder>(recorder)
+            .unwrap()
+            .0
+            .clone()
+    }
+
+    #[wasm_bindgen_test]
+    fn reverted_transactions_are_detected() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        let recorder = sim.add_event_handler(RevertedTxRecorder::default());
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        // node1 confirms a transaction while node2 secretly mines a longer chain
+        sim.do_now(ForSpecific(
+            node1,
+            BuildAndBroadcastTransaction::from("Alice", "Bob", 42),
+        ));
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(10.);
+        let state1 = get_state(&sim, node1);
+        let tx = *state1.txes_confirmed.iter().next().unwrap();
+        assert_eq!(Some(0), state1.confirmations(tx));
+        assert_eq!(0, sim.reverted_tx_count());
+
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        sim.do_now(ResyncAll);
+        sim.catch_up(100.);
+
         let state1 = get_state(&sim, node1);
-        let state2 = get_state(&sim, node2);
+        assert_eq!(None, state1.confirmations(tx));
+        assert_eq!(&[tx], state1.reverted_txes());
+        assert_eq!(0, get_state(&sim, node2).reverted_tx_count());
+        assert_eq!(1, sim.reverted_tx_count());
+        assert!(sim
+            .logger
+            .entries()
+            .any(|entry| entry.message.contains("was reverted with block")));
+        assert_eq!(vec![(node1, tx)], reverted_tx_events(&sim, recorder));
+    }
 
-        assert_eq!(state1.height(state1.tip), state2.height(state2.tip));
-        assert_eq!(state1.tip, state2.tip);
+    #[wasm_bindgen_test]
+    fn injected_chains_are_checked_for_reverted_transactions() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        let recorder = sim.add_event_handler(RevertedTxRecorder::default());
+        let node = sim.spawn_random_node();
+        sim.do_now(ForSpecific(
+            node,
+            BuildAndBroadcastTransaction::from("Alice", "Bob", 42),
+        ));
+        sim.do_now(ForSpecific(node, MineBlock));
+        sim.catch_up(10.);
+        let tx = *get_state(&sim, node).txes_confirmed.iter().next().unwrap();
+
+        let attacker_block1 = BlockHeader {
+            id: sim.world.reserve_entity(),
+            id_prev: None,
+            genesis: None,
+            height: 1,
+            difficulty: 1,
+            chainwork: 1,
+            miner: node,
+            timestamp: sim.time.now(),
+        };
+        let attacker_block2 = BlockHeader {
+            id: sim.world.reserve_entity(),
+            id_prev: Some(attacker_block1.id),
+            height: 2,
+            chainwork: 2,
+            ..attacker_block1
+        };
+        for header in [attacker_block1, attacker_block2] {
+            sim.do_now(InjectBlock {
+                node,
+                header,
+                contents: BlockContents::new(),
+            });
+        }
+        sim.catch_up(10.);
+
+        assert_eq!(attacker_block2.id, get_state(&sim, node).tip());
+        assert_eq!(vec![(node, tx)], reverted_tx_events(&sim, recorder));
+    }
+
+    #[wasm_bindgen_test]
+    fn transactions_in_orphaned_forks_have_no_confirmations() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let tx =
+            sim.node_interface(node)
+                .spawn_transaction("Alice".to_string(), "Bob".to_string(), 42);
+        let mut state = NakamotoNodeState::new(&sim);
+
+        let block1 = spawn_and_register_block(&mut sim, &mut state, node, None, vec![]);
+        spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![tx]);
+        assert_eq!(Some(0), state.confirmations(tx));
+
+        let block2 = spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![]);
+        spawn_and_register_block(&mut sim, &mut state, node, Some(block2), vec![]);
+
+        assert_eq!(3, state.tip_height());
+        assert_eq!(None, state.confirmations(tx));
+    }
+
+    #[wasm_bindgen_test]
+    fn block_of_tx_prefers_main_chain() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let mut node_interface = sim.node_interface(node);
+        let tx1 = node_interface.spawn_transaction("Alice".to_string(), "Bob".to_string(), 1);
+        let tx2 = node_interface.spawn_transaction("Bob".to_string(), "Carol".to_string(), 2);
+        let tx3 = node_interface.spawn_transaction("Carol".to_string(), "Dave".to_string(), 3);
+        let mut state = NakamotoNodeState::new(&sim);
+
+        let block1 = spawn_and_register_block(&mut sim, &mut state, node, None, vec![tx1]);
+        let fork = spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![tx2]);
+        let block2 = spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![]);
+        let block3 = spawn_and_register_block(&mut sim, &mut state, node, Some(block2), vec![tx2]);
+        assert!(state.fork_tips().contains(&fork));
+
+        assert_eq!(Some(block1), state.block_of_tx(tx1));
+        assert_eq!(Some(block3), state.block_of_tx(tx2));
+        assert_eq!(None, state.block_of_tx(tx3));
+
+        spawn_and_register_block(&mut sim, &mut state, node, Some(fork), vec![tx3]);
+        assert_eq!(None, state.confirmations(tx3));
+        assert!(state.block_of_tx(tx3).is_some());
+    }
+
+    #[wasm_bindgen_test]
+    fn main_chain_leads_from_genesis_to_tip() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let mut state = NakamotoNodeState::new(&sim);
+        let genesis = sim.default_genesis_block();
+        assert_eq!(vec![genesis], state.main_chain());
+
+        let block1 = spawn_and_register_block(&mut sim, &mut state, node, None, vec![]);
+        let block2 = spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![]);
+        spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![]); // fork
+        let block3 = spawn_and_register_block(&mut sim, &mut state, node, Some(block2), vec![]);
+
+        let main_chain = state.main_chain();
+        assert_eq!(state.tip_height() + 1, main_chain.len());
+        assert_eq!(vec![genesis, block1, block2, block3], main_chain);
+    }
+
+    #[wasm_bindgen_test]
+    fn genesis_block_is_known_at_height_0() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let mut state = NakamotoNodeState::new(&sim);
+        let genesis = sim.default_genesis_block();
+        assert_eq!(genesis, state.genesis_block());
+        assert_eq!(genesis, state.tip());
+        assert!(state.is_at_genesis());
+        assert_eq!(vec![genesis], state.known_blocks_sorted());
+        assert_eq!(0, state.height(genesis));
+        assert_eq!(0, state.known_block_count());
+
+        let block1 = spawn_and_register_block(&mut sim, &mut state, node, None, vec![]);
+        assert_eq!(vec![genesis, block1], state.known_blocks_sorted());
+        assert_eq!(Some(genesis), state.previous_block(block1));
+        assert_eq!(None, state.previous_block(genesis));
+        assert_eq!(Some(genesis), state.common_ancestor(block1, genesis));
+
+        // other genesis blocks are explicit, too
+        let other_genesis = sim.spawn_genesis_block();
+        let state = NakamotoNodeState::new_with_genesis(other_genesis);
+        assert_eq!(vec![other_genesis], state.main_chain());
+        assert_eq!(
+            Some(0),
+            state
+                .block_header(other_genesis)
+                .map(|header| header.height)
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn injected_blocks_get_distributed_unless_orphaned() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+
+        let header = BlockHeader {
+            id: sim.world.reserve_entity(),
+            id_prev: None,
+            genesis: None,
+            height: 1,
+            difficulty: 1,
+            chainwork: 1,
+            miner: node1,
+            timestamp: OrderedFloat(0.),
+        };
+        sim.do_now(InjectBlock {
+            node: node1,
+            header,
+            contents: BlockContents::new(),
+        });
+        sim.catch_up(100.);
+
+        assert_eq!(header.id, get_state(&sim, node1).tip());
+        assert_eq!(header.id, get_state(&sim, node2).tip());
+
+        let orphan = BlockHeader {
+            id: sim.world.reserve_entity(),
+            id_prev: Some(sim.world.reserve_entity()),
+            height: 5,
+            ..header
+        };
+        sim.do_now(InjectBlock {
+            node: node1,
+            header: orphan,
+            contents: BlockContents::new(),
+        });
+        sim.catch_up(100.);
+
+        assert_eq!(None, get_state(&sim, node1).block_header(orphan.id));
+        assert_eq!(header.id, get_state(&sim, node1).tip());
+    }
+
+    #[wasm_bindgen_test]
+    fn fork_depths_are_measured_from_tip_height() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let mut state = NakamotoNodeState::new(&sim);
+        assert!(state.fork_depths().is_empty());
+
+        let block1 = spawn_and_register_block(&mut sim, &mut state, node, None, vec![]);
+        let mut block = spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![]);
+        spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![]);
+        for _ in 0..2 {
+            block = spawn_and_register_block(&mut sim, &mut state, node, Some(block), vec![]);
+        }
+        spawn_and_register_block(&mut sim, &mut state, node, None, vec![]);
+
+        assert_eq!(4, state.tip_height());
+        assert_eq!(vec![2, 3], state.fork_depths());
+    }
+
+    #[wasm_bindgen_test]
+    fn heavier_chain_wins_over_longer_chain() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let mut state = NakamotoNodeState::new(&sim);
+
+        let mut light_tip = None;
+        for _ in 0..3 {
+            light_tip = Some(spawn_and_register_block(
+                &mut sim,
+                &mut state,
+                node,
+                light_tip,
+                vec![],
+            ));
+        }
+        assert_eq!(3, state.tip_chainwork());
+
+        let heavy = sim
+            .node_interface(node)
+            .spawn_block_with_difficulty(None, 2, []);
+        assert!(!state.register_block(heavy, BlockContents::new()));
+        assert_eq!(light_tip, Some(state.tip()));
+
+        let heavy = sim.node_interface(node).spawn_block(Some(heavy.id), []);
+        assert!(state.register_block(heavy, BlockContents::new()));
+        assert_eq!(heavy.id, state.tip());
+        assert_eq!(2, state.tip_height());
+        assert_eq!(4, state.tip_chainwork());
+        assert!(state.fork_tips().contains(&light_tip.unwrap()));
+    }
+
+    #[wasm_bindgen_test]
+    fn average_block_interval_follows_main_chain_timestamps() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let mut state = NakamotoNodeState::new(&sim);
+        assert_eq!(None, state.average_block_interval());
+
+        sim.work_until(OrderedFloat(10.));
+        let mut block = spawn_and_register_block(&mut sim, &mut state, node, None, vec![]);
+        assert_eq!(None, state.average_block_interval());
+        let first_block = block;
+        for time in [20., 40.] {
+            sim.work_until(OrderedFloat(time));
+            block = spawn_and_register_block(&mut sim, &mut state, node, Some(block), vec![]);
+        }
+        // a fork block doesn't count
+        sim.work_until(OrderedFloat(100.));
+        spawn_and_register_block(&mut sim, &mut state, node, Some(first_block), vec![]);
+
+        assert_eq!(Some(15.), state.average_block_interval());
+    }
+
+    #[wasm_bindgen_test]
+    fn tip_accessors_are_consistent() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let mut state = NakamotoNodeState::new(&sim);
+        assert_eq!(0, state.tip_height());
+        assert_eq!(state.genesis_block(), state.tip_header().id);
+
+        let block1 = spawn_and_register_block(&mut sim, &mut state, node, None, vec![]);
+        let block2 = spawn_and_register_block(&mut sim, &mut state, node, Some(block1), vec![]);
+
+        let tip_header = state.tip_header();
+        assert_eq!(2, state.tip_height());
+        assert_eq!(state.height(state.tip()), state.tip_height());
+        assert_eq!(block2, tip_header.id);
+        assert_eq!(Some(block1), tip_header.id_prev);
+        assert_eq!(Some(tip_header), state.block_header(block2));
+        assert_eq!(None, state.block_header(node));
+    }
+
+    #[wasm_bindgen_test]
+    fn chain_quality_counts_blocks_of_node() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+
+        for miner in [node1, node2, node1, node1] {
+            sim.do_now(ForSpecific(miner, MineBlock));
+            sim.catch_up(100.);
+        }
+
+        let state = get_state(&sim, node2);
+
+        assert_eq!(0.75, state.chain_quality(node1, 4));
+        assert_eq!(0.25, state.chain_quality(node2, 4));
+        assert_eq!(1., state.chain_quality(node1, 2));
+        assert_eq!(0.75, state.chain_quality(node1, 100));
+    }
+
+    #[wasm_bindgen_test]
+    fn chain_growth_rate_is_blocks_per_second() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        sim.time.set_speed(1.);
+
+        let node = sim.spawn_random_node();
+        for time in [10., 20., 30.] {
+            sim.do_at(SimSeconds::from(time), ForSpecific(node, MineBlock));
+        }
+        sim.catch_up(100.);
+
+        let state = get_state(&sim, node);
+
+        assert_eq!(0.1, state.chain_growth_rate());
+    }
+
+    #[wasm_bindgen_test]
+    fn dot_export_contains_all_blocks_and_styles_forks() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(100.);
+
+        let state = get_state(&sim, node1);
+        let first_block = state.known_blocks_sorted()[1];
+        let fork_tip = *state.fork_tips().iter().next().unwrap();
+        let dot = state.to_dot();
+
+        assert!(dot.starts_with("digraph"));
+        assert!(dot.contains(&format!("b{} -> genesis;", first_block.id())));
+        assert!(dot.contains(&format!("b{} -> b{};", fork_tip.id(), first_block.id())));
+        assert_eq!(
+            3,
+            dot.matches("style=bold").count() + dot.matches("style=dashed").count()
+        );
+        assert_eq!(1, dot.matches("style=dashed").count());
+    }
+
+    #[wasm_bindgen_test]
+    fn tip_height_sampler_sees_converged_heights_in_final_sample() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        // sampling on every event, so the last sample is taken after the last block arrived
+        let sampler_index = sim.add_event_handler(TipHeightSampler::new(SimSeconds::from(0.)));
+
+        sim.do_now(SpawnRandomNodes(8));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+
+        for _ in 0..3 {
+            sim.do_now(ForRandomNode(MineBlock));
+            sim.catch_up(100.);
+        }
+
+        let handlers = sim.additional_event_handlers();
+        let handlers = handlers.borrow();
+        let sampler = handlers.get::<TipHeightSampler>(sampler_index).unwrap();
+
+        let final_sample: Vec<usize> = sampler
+            .samples()
+            .iter()
+            .rev()
+            .take(8)
+            .map(|&(_, _, tip_height)| tip_height)
+            .collect();
+
+        assert_eq!(vec![3; 8], final_sample);
+        assert!(sampler
+            .export_csv()
+            .starts_with("sim_time,node,tip_height\n"));
+        assert_eq!(
+            sampler.samples().len() + 1,
+            sampler.export_csv().lines().count()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn removed_peer_receives_no_further_queued_blocks() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        for _ in 0..10 {
+            sim.do_now(ForSpecific(node1, MineBlock));
+        }
+        sim.catch_up(10.);
+
+        // the blocks are sent out 1ms apart from each other
+        let start_time = sim.time.now();
+        sim.add_peer(node1, node2);
+        sim.work_until(start_time + 0.0035);
+        sim.remove_peer(node1, node2);
+        sim.work_until(start_time + 100.);
+
+        let state2 = get_state(&sim, node2);
+
+        assert!(state2.tip_height() > 0);
+        assert!(state2.tip_height() < 10);
+        assert_eq!(0, sim.world.query::<&UnderlayMessage>().iter().count());
+    }
+
+    #[wasm_bindgen_test]
+    fn common_ancestor_of_forks_is_found() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+        let ancestor = get_state(&sim, node1).tip();
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(100.);
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+
+        let state = get_state(&sim, node2);
+        let tip = state.tip();
+        let fork_tip = *state.fork_tips().iter().next().unwrap();
+
+        assert_eq!(Some(ancestor), state.common_ancestor(tip, fork_tip));
+        assert_eq!(Some(ancestor), state.common_ancestor(fork_tip, tip));
+        assert_eq!(Some(tip), state.common_ancestor(tip, tip));
+        assert_eq!(Some(ancestor), state.common_ancestor(ancestor, tip));
+    }
+
+    #[wasm_bindgen_test]
+    fn all_tips_are_listed_by_height() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        let genesis = sim.default_genesis_block();
+        assert_eq!(vec![(genesis, 0)], NakamotoNodeState::new(&sim).all_tips());
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(100.);
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+
+        let state = get_state(&sim, node2);
+        let fork_tip = *state.fork_tips().iter().next().unwrap();
+        assert_eq!(vec![(state.tip(), 3), (fork_tip, 2)], state.all_tips());
+    }
+
+    #[wasm_bindgen_test]
+    fn node_status_reflects_height() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+        let protocol = NakamotoConsensus::default();
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        assert_eq!(
+            "h=0 forks=0",
+            protocol.node_status(sim.node_interface(node2))
+        );
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+
+        assert_eq!(
+            "h=2 forks=0",
+            protocol.node_status(sim.node_interface(node2))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn fork_blocks_end_at_common_ancestor() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+
+        // node2 mines a fork of two blocks while node1 mines three on its own
+        sim.remove_peer(node1, node2);
+        sim.remove_peer(node2, node1);
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(1.);
+        let fork_block1 = get_state(&sim, node2).tip();
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(1.);
+        let fork_block2 = get_state(&sim, node2).tip();
+        for _ in 0..3 {
+            sim.do_now(ForSpecific(node1, MineBlock));
+            sim.catch_up(1.);
+        }
+
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        sim.do_now(ResyncAll);
+        sim.catch_up(100.);
+
+        let state = get_state(&sim, node1);
+        assert_eq!(
+            vec![fork_block2, fork_block1],
+            state.fork_blocks(fork_block2)
+        );
+        assert_eq!(vec![fork_block1], state.fork_blocks(fork_block1));
+        assert!(state.fork_blocks(state.tip()).is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn common_ancestor_of_unrelated_chains_is_genesis() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_now(ForSpecific(node2, MineBlock));
+        sim.catch_up(10.);
+
+        let tip2 = get_state(&sim, node2).tip();
+
+        sim.add_peer(node2, node1);
+        sim.catch_up(10.);
+
+        let state1 = get_state(&sim, node1);
+
+        let genesis = sim.default_genesis_block();
+        assert_eq!(Some(genesis), state1.common_ancestor(state1.tip(), tip2));
+        assert_eq!(None, state1.common_ancestor(state1.tip(), node1));
+    }
+
+    #[wasm_bindgen_test]
+    fn resync_all_brings_silently_connected_node_up_to_date() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.do_now(ForSpecific(node1, MineBlock));
+        sim.catch_up(100.);
+
+        // connecting without triggering `PeerSetChanged` events
+        let node3 = sim.spawn_random_node();
+        let now = sim.time.now();
+        sim.peers_mut(node2).insert(node3, now);
+        sim.peers_mut(node3).insert(node2, now);
+        sim.catch_up(100.);
+
+        assert!(sim.world.get::<NakamotoNodeState>(node3).is_err());
+
+        sim.do_now(ResyncAll);
+        sim.catch_up(100.);
+
+        let state1 = get_state(&sim, node1);
+        let state3 = get_state(&sim, node3);
+
+        assert_eq!(2, state3.tip_height());
+        assert_eq!(state1.tip, state3.tip);
     }
 }
@@ -32,16 +32,7 @@ impl Protocol for RandomWalks {
 }
 
 pub fn random_step(node: &mut NodeInterface, current_ttl: usize) -> Result<Entity, String> {
-    if let Some(dest) = random_peer(node) {
-        Ok(node.send_message(dest, RandomWalkMessage::new(current_ttl - 1)))
-    } else {
-        Err("Couldn't find a suitable message destination. Not enough peers?".to_string())
-    }
-}
-
-fn random_peer(node: &mut NodeInterface) -> Option<Entity> {
-    let peers = node.get::<PeerSet>().clone(); // TODO: the `.clone()` here is not ideal
-    peers.iter().choose(node.rng()).copied()
+    node.send_message_to_random_peer(RandomWalkMessage::new(current_ttl - 1))
 }
 
 #[derive(Debug, Copy, Clone)]
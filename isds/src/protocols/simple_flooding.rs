@@ -4,14 +4,41 @@ use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 use std::marker::PhantomData;
 
+/// To how many peers (that don't have it yet) a node relays a received item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FanOut {
+    All,
+    /// The square root of the number of peers (rounded up).
+    Sqrt,
+    Fixed(usize),
+}
+impl FanOut {
+    fn limit(&self, peer_count: usize) -> usize {
+        match *self {
+            Self::All => peer_count,
+            Self::Sqrt => (peer_count as f64).sqrt().ceil() as usize,
+            Self::Fixed(fan_out) => fan_out,
+        }
+    }
+}
+
+/// Nodes push new items to their peers. With a limited `FanOut`, received items are only relayed
+/// to a random subset of the peers, which saves bandwidth but no longer guarantees that every
+/// node gets every item: all peers relaying to some node might happen to skip it. Items are
+/// always pushed to all peers by the node that floods them first, though.
 #[derive(Debug)]
 pub struct SimpleFlooding<T: Payload> {
     payload_type: PhantomData<T>,
+    fan_out: FanOut,
 }
 impl<T: Payload> SimpleFlooding<T> {
     pub fn new() -> Self {
+        Self::new_with_fan_out(FanOut::All)
+    }
+    pub fn new_with_fan_out(fan_out: FanOut) -> Self {
         Self {
             payload_type: PhantomData,
+            fan_out,
         }
     }
 }
@@ -41,8 +68,13 @@ impl<T: Payload + Hash + Eq> Protocol for SimpleFlooding<T> {
     ) -> Result<(), Box<dyn Error>> {
         let message = message_payload.0;
         register_sender(&mut node, &message, underlay_message.source);
-        if is_new(&mut node, &message) {
-            Self::flood(&mut node, message);
+        let is_new = is_new(&mut node, &message);
+        let flooding_state = node.get::<SimpleFloodingState<T>>();
+        flooding_state.received += 1;
+        if is_new {
+            Self::flood_with_fan_out(&mut node, message, self.fan_out);
+        } else {
+            flooding_state.duplicates += 1;
         }
         Ok(())
     }
@@ -70,30 +102,98 @@ impl<T: Payload + Hash + Eq> Protocol for SimpleFlooding<T> {
 #[derive(Debug, Default, Clone)]
 pub struct SimpleFloodingMessage<T>(pub T);
 
+/// Flooded items that refer to an entity (such as a block or a transaction). Lets `NetView` draw
+/// the messages of any protocol that floods such items, see `inventory_messages`.
+pub trait Inventory: Payload + Hash + Eq {
+    /// The entity the item refers to, used for coloring and highlighting its messages.
+    fn entity(&self) -> Entity;
+    /// Prominent items (such as blocks) are drawn larger and in their entity's color.
+    fn is_prominent(&self) -> bool {
+        true
+    }
+}
+
+/// A flooded inventory message, as far as drawing it is concerned.
+#[derive(Debug, Clone, Copy)]
+pub struct InventoryMessage {
+    pub message: Entity,
+    pub trajectory: UnderlayLine,
+    pub time_span: TimeSpan,
+    /// The flooded item.
+    pub entity: Entity,
+    pub is_prominent: bool,
+}
+
+/// All messages flooding items of type `T` that departed by `time_now`.
+pub fn inventory_messages<T: Inventory>(
+    world: &World,
+    time_now: SimSeconds,
+) -> Vec<InventoryMessage> {
+    world
+        .query::<(&UnderlayLine, &TimeSpan, &SimpleFloodingMessage<T>)>()
+        .into_iter()
+        // queued messages (e.g., later legs of routed messages) aren't drawn before departing
+        .filter(|(_, (_, time_span, _))| time_span.start <= time_now)
+        .map(
+            |(id, (&trajectory, &time_span, message))| InventoryMessage {
+                message: id,
+                trajectory,
+                time_span,
+                entity: message.0.entity(),
+                is_prominent: message.0.is_prominent(),
+            },
+        )
+        .collect()
+}
+
 // TODO: also clear messages from seen set at some point? or isn't that "simple" anymore?
 #[derive(Debug, Clone)]
 pub struct SimpleFloodingState<T> {
     pub own_haves: HashSet<T>,
     peer_haves: HashMap<Entity, HashSet<T>>,
+    received: usize,
+    /// Received messages carrying items the node already had.
+    duplicates: usize,
 }
 impl<T> Default for SimpleFloodingState<T> {
     fn default() -> Self {
         Self {
             own_haves: Default::default(),
             peer_haves: Default::default(),
+            received: 0,
+            duplicates: 0,
         }
     }
 }
+impl<T> SimpleFloodingState<T> {
+    pub fn received(&self) -> usize {
+        self.received
+    }
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+}
 
 impl<T: Payload + Hash + Eq> SimpleFlooding<T> {
     pub fn flood(node: &mut NodeInterface, message: T) {
-        let peers = node.get::<PeerSet>().clone(); // TODO: again, the clone here is not ideal
+        Self::flood_with_fan_out(node, message, FanOut::All);
+    }
+    /// Like `flood`, but only sends to a random subset of the peers that don't have the item yet.
+    pub fn flood_with_fan_out(node: &mut NodeInterface, message: T, fan_out: FanOut) {
+        let mut peers: Vec<Entity> = node.get::<PeerSet>().iter().copied().collect();
+        let limit = fan_out.limit(peers.len());
+        if limit < peers.len() {
+            peers.shuffle(node.rng());
+        }
         let flooding_state = node.get::<SimpleFloodingState<T>>();
 
         let mut next_hops = vec![];
 
         flooding_state.own_haves.insert(message.clone());
         for peer in peers.into_iter() {
+            if next_hops.len() == limit {
+                break;
+            }
             match flooding_state.peer_haves.entry(peer) {
                 Entry::Occupied(mut e) => {
                     if e.get_mut().insert(message.clone()) {
@@ -109,9 +209,33 @@ impl<T: Payload + Hash + Eq> SimpleFlooding<T> {
             }
         }
         for peer in next_hops.into_iter() {
-            node.send_message(peer, SimpleFloodingMessage(message.clone()));
+            if let Err(e) = node.send_message(peer, SimpleFloodingMessage(message.clone())) {
+                node.log(&format!("Skipping peer while flooding: {}", e));
+            }
         }
     }
+    /// Whether the node has received (or flooded) the item yet, e.g., for measuring how far it
+    /// has propagated.
+    pub fn holds(node: &mut NodeInterface, item: &T) -> bool {
+        node.try_get::<SimpleFloodingState<T>>()
+            .is_some_and(|flooding_state| flooding_state.own_haves.contains(item))
+    }
+    /// All items the node has received (or flooded), in no particular order.
+    pub fn held_items(node: &mut NodeInterface) -> Vec<T> {
+        node.try_get::<SimpleFloodingState<T>>()
+            .map_or(vec![], |flooding_state| {
+                flooding_state.own_haves.iter().cloned().collect()
+            })
+    }
+    /// The fraction of received messages that carried items the node already had, i.e., how
+    /// much of flooding's traffic was wasted on it. `0.` if it hasn't received anything yet.
+    pub fn redundancy_ratio(node: &mut NodeInterface) -> f64 {
+        node.try_get::<SimpleFloodingState<T>>()
+            .filter(|flooding_state| flooding_state.received > 0)
+            .map_or(0., |flooding_state| {
+                flooding_state.duplicates as f64 / flooding_state.received as f64
+            })
+    }
     pub fn forget_peer(node: &mut NodeInterface, peer: Entity) {
         let flooding_state = node.get::<SimpleFloodingState<T>>();
         flooding_state.peer_haves.remove(&peer);
@@ -126,7 +250,9 @@ impl<T: Payload + Hash + Eq> SimpleFlooding<T> {
             .entry(peer)
             .or_default()
             .extend(items.clone().into_iter());
-        node.send_messages(peer, items.into_iter().map(SimpleFloodingMessage));
+        if let Err(e) = node.send_messages(peer, items.into_iter().map(SimpleFloodingMessage)) {
+            node.log(&format!("Skipping peer while flooding: {}", e));
+        }
     }
 }
 
@@ -185,6 +311,162 @@ mod tests {
         assert_eq!(8, as_expected_nodes.count());
     }
 
+    #[wasm_bindgen_test]
+    fn flooded_items_are_held_by_reachable_nodes() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(SimpleFlooding::<u32>::new()));
+        sim.do_now(SpawnRandomNodes(8));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+        let connected_nodes = sim.all_nodes();
+        let isolated_node = sim.spawn_random_node();
+
+        let start_node = connected_nodes[0];
+        SimpleFlooding::<u32>::flood(&mut sim.node_interface(start_node), 42);
+        assert!(SimpleFlooding::<u32>::holds(
+            &mut sim.node_interface(start_node),
+            &42
+        ));
+        assert!(!SimpleFlooding::<u32>::holds(
+            &mut sim.node_interface(connected_nodes[1]),
+            &42
+        ));
+        sim.catch_up(1000.);
+
+        for node in connected_nodes {
+            let mut node = sim.node_interface(node);
+            assert!(SimpleFlooding::<u32>::holds(&mut node, &42));
+            assert!(!SimpleFlooding::<u32>::holds(&mut node, &23));
+            assert_eq!(vec![42], SimpleFlooding::<u32>::held_items(&mut node));
+        }
+        let mut isolated_node = sim.node_interface(isolated_node);
+        assert!(!SimpleFlooding::<u32>::holds(&mut isolated_node, &42));
+        assert!(SimpleFlooding::<u32>::held_items(&mut isolated_node).is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn flooding_in_cycles_delivers_duplicates() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(SimpleFlooding::<u32>::new()));
+        let nodes: Vec<Entity> = (0..4).map(|_| sim.spawn_random_node()).collect();
+        for i in 0..nodes.len() {
+            let (node1, node2) = (nodes[i], nodes[(i + 1) % nodes.len()]);
+            sim.add_peer(node1, node2);
+            sim.add_peer(node2, node1);
+        }
+        sim.catch_up(1.);
+        assert_eq!(
+            0.,
+            SimpleFlooding::<u32>::redundancy_ratio(&mut sim.node_interface(nodes[0]))
+        );
+
+        SimpleFlooding::<u32>::flood(&mut sim.node_interface(nodes[0]), 42);
+        sim.catch_up(1000.);
+
+        let (received, duplicates) = nodes
+            .iter()
+            .map(|&node| {
+                let state = sim.world.get::<SimpleFloodingState<u32>>(node).unwrap();
+                (state.received(), state.duplicates())
+            })
+            .fold((0, 0), |(r, d), (received, duplicates)| {
+                (r + received, d + duplicates)
+            });
+        // every node but the source gets the item once, the rest is redundant
+        assert_eq!(nodes.len() - 1, received - duplicates);
+        assert!(duplicates > 0);
+        // the node opposite of the source gets the item from both sides
+        let mut opposite = sim.node_interface(nodes[2]);
+        assert_eq!(0.5, SimpleFlooding::<u32>::redundancy_ratio(&mut opposite));
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Announcement(Entity);
+    impl Inventory for Announcement {
+        fn entity(&self) -> Entity {
+            self.0
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn inventory_messages_of_custom_items_are_collected() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(
+            SimpleFlooding::<Announcement>::new(),
+        ));
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.work_until(OrderedFloat(1.));
+
+        let mut node = sim.node_interface(node1);
+        // any entity will do
+        let announced = node.spawn_transaction("alice".to_string(), "bob".to_string(), 1);
+        SimpleFlooding::flood(&mut node, Announcement(announced));
+
+        let messages = inventory_messages::<Announcement>(&sim.world, sim.time.now());
+        assert_eq!(1, messages.len());
+        assert_eq!(announced, messages[0].entity);
+        assert!(messages[0].is_prominent);
+        assert!(
+            inventory_messages::<crate::nakamoto_consensus::InventoryItem>(
+                &sim.world,
+                sim.time.now()
+            )
+            .is_empty()
+        );
+    }
+
+    #[derive(Default)]
+    struct MessageCounter(usize);
+    impl EventHandler for MessageCounter {
+        fn handle_event(
+            &mut self,
+            _: &mut Simulation,
+            event: simulation::Event,
+        ) -> Result<(), Box<dyn Error>> {
+            if let simulation::Event::Node(_, NodeEvent::MessageSent(_)) = event {
+                self.0 += 1;
+            }
+            Ok(())
+        }
+    }
+
+    /// Floods a single value through a fixed network, returning the number of sent messages.
+    fn count_flooding_messages(sim: &mut Simulation, fan_out: FanOut) -> usize {
+        sim.add_event_handler(InvokeProtocolForAllNodes(
+            SimpleFlooding::<u32>::new_with_fan_out(fan_out),
+        ));
+        let counter = sim.add_event_handler(MessageCounter::default());
+        let start_node = sim.pick_random_node().unwrap();
+        SimpleFlooding::<u32>::flood(&mut sim.node_interface(start_node), 42);
+        sim.catch_up(1000.);
+
+        let handlers = sim.additional_event_handlers();
+        let handlers = handlers.borrow();
+        handlers.get::<MessageCounter>(counter).unwrap().0
+    }
+
+    #[wasm_bindgen_test]
+    fn limited_fan_out_sends_fewer_messages() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(100));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+        let mut sqrt_sim = Simulation::new();
+        for (_, _, position) in sim.nodes() {
+            sqrt_sim.spawn_random_node_at_position(position.x, position.y);
+        }
+        sqrt_sim.do_now(MakeDelaunayNetwork);
+        sqrt_sim.catch_up(1.);
+
+        let full_flooding_messages = count_flooding_messages(&mut sim, FanOut::All);
+        let sqrt_flooding_messages = count_flooding_messages(&mut sqrt_sim, FanOut::Sqrt);
+
+        assert!(sqrt_flooding_messages > 0);
+        assert!(sqrt_flooding_messages < full_flooding_messages);
+    }
+
     #[wasm_bindgen_test]
     fn simple_flooding_recovers_from_splits() {
         let mut sim = Simulation::new();
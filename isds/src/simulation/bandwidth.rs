@@ -0,0 +1,117 @@
+use super::*;
+use std::collections::HashMap;
+
+/// Keeps track of how many bytes each node sent and received within the last `window` sim
+/// seconds, e.g., for spotting bottlenecks or comparing the efficiency of protocols. How many
+/// bytes a message has is determined by the `message_size` function.
+pub struct BandwidthMeter {
+    window: SimSeconds,
+    message_size: fn(Entity, &World) -> usize,
+    sent: HashMap<Entity, VecDeque<(SimSeconds, usize)>>,
+    received: HashMap<Entity, VecDeque<(SimSeconds, usize)>>,
+}
+impl BandwidthMeter {
+    pub fn new(window: SimSeconds, message_size: fn(Entity, &World) -> usize) -> Self {
+        Self {
+            window,
+            message_size,
+            sent: HashMap::new(),
+            received: HashMap::new(),
+        }
+    }
+    /// Bytes per sim second received by `node` within the window before `now`.
+    pub fn bandwidth_in(&self, node: Entity, now: SimSeconds) -> f64 {
+        self.bandwidth(&self.received, node, now)
+    }
+    /// Bytes per sim second sent by `node` within the window before `now`.
+    pub fn bandwidth_out(&self, node: Entity, now: SimSeconds) -> f64 {
+        self.bandwidth(&self.sent, node, now)
+    }
+    fn bandwidth(
+        &self,
+        records: &HashMap<Entity, VecDeque<(SimSeconds, usize)>>,
+        node: Entity,
+        now: SimSeconds,
+    ) -> f64 {
+        let bytes: usize = records.get(&node).map_or(0, |records| {
+            records
+                .iter()
+                .filter(|&&(time, _)| time > now - self.window)
+                .map(|&(_, bytes)| bytes)
+                .sum()
+        });
+        bytes as f64 / self.window.into_inner()
+    }
+    fn record(
+        records: &mut HashMap<Entity, VecDeque<(SimSeconds, usize)>>,
+        node: Entity,
+        now: SimSeconds,
+        bytes: usize,
+        window: SimSeconds,
+    ) {
+        let node_records = records.entry(node).or_default();
+        node_records.push_back((now, bytes));
+        while node_records
+            .front()
+            .filter(|&&(time, _)| time <= now - window)
+            .is_some()
+        {
+            node_records.pop_front();
+        }
+    }
+}
+impl EventHandler for BandwidthMeter {
+    fn handle_event(&mut self, sim: &mut Simulation, event: Event) -> Result<(), Box<dyn Error>> {
+        if let Event::Node(node, event) = event {
+            let now = sim.time.now();
+            match event {
+                // queued messages might have been dropped before departing
+                NodeEvent::MessageSent(message) if sim.world.contains(message) => {
+                    let bytes = (self.message_size)(message, &sim.world);
+                    Self::record(&mut self.sent, node, now, bytes, self.window);
+                }
+                // the message might have been dropped before departing
+                NodeEvent::MessageArrived(message) if sim.world.contains(message) => {
+                    let bytes = (self.message_size)(message, &sim.world);
+                    Self::record(&mut self.received, node, now, bytes, self.window);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    fn reset(&mut self, _: &mut Simulation) {
+        self.sent.clear();
+        self.received.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn bandwidth_is_measured_within_window() {
+        let mut sim = Simulation::new();
+        let meter = sim.add_event_handler(BandwidthMeter::new(OrderedFloat(10.), |_, _| 100));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        for _ in 0..3 {
+            sim.send_message(node1, node2, ()).unwrap();
+        }
+        sim.work_until(OrderedFloat(5.));
+
+        let handlers = sim.additional_event_handlers();
+        let handlers = handlers.borrow();
+        let meter = handlers.get::<BandwidthMeter>(meter).unwrap();
+        let now = sim.time.now();
+        assert_eq!(30., meter.bandwidth_out(node1, now));
+        assert_eq!(30., meter.bandwidth_in(node2, now));
+        assert_eq!(0., meter.bandwidth_in(node1, now));
+        assert_eq!(0., meter.bandwidth_out(node1, OrderedFloat(20.)));
+    }
+}
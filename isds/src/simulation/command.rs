@@ -1,6 +1,9 @@
 use super::*;
 use dyn_clone::DynClone;
 
+/// How long `run_script` keeps processing events after the last scripted command, at most.
+const SCRIPT_GRACE_PERIOD: f64 = 1000.;
+
 pub trait Command: DynClone + std::fmt::Debug + Sync + Send {
     fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>>;
 }
@@ -18,6 +21,16 @@ impl<A: EntityAction> Command for ForSpecific<A> {
     }
 }
 
+/// Starts over with an empty simulation; see `Simulation::clear`.
+#[derive(Debug, Clone)]
+pub struct Reset;
+impl Command for Reset {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.clear();
+        Ok(())
+    }
+}
+
 impl Simulation {
     pub fn do_now(&mut self, command: impl Command + 'static) {
         self.do_at(self.time.now(), command)
@@ -26,21 +39,55 @@ impl Simulation {
         self.do_at(self.time.now() + duration, command)
     }
     pub fn do_at(&mut self, time_due: SimSeconds, command: impl Command + 'static) {
-        let boxed_command: Box<dyn Command> = Box::new(command);
-        let command_entry = self.world.spawn((time_due, boxed_command));
+        self.do_at_boxed(time_due, Box::new(command))
+    }
+    pub fn do_at_boxed(&mut self, time_due: SimSeconds, command: Box<dyn Command>) {
+        let command_entry = self.world.spawn((time_due, command));
+        if !self.handling_event {
+            self.world
+                .insert_one(command_entry, recorder::IssuedCommand)
+                .unwrap();
+        }
         self.schedule_at(time_due, Event::Command(command_entry))
     }
+    /// Executes each command of the scenario at the given time (relative to now) and works until
+    /// all resulting events have been processed, e.g., until all messages have arrived. Gives up
+    /// `SCRIPT_GRACE_PERIOD` after the last command, so scripts can contain repeating commands.
+    pub fn run_script(&mut self, script: Vec<(SimSeconds, Box<dyn Command>)>) {
+        let script_duration = script
+            .iter()
+            .map(|&(time, _)| time)
+            .max()
+            .unwrap_or_default();
+        for (time, command) in script {
+            self.do_at_boxed(self.time.now() + time, command);
+        }
+        let timeout = script_duration + OrderedFloat(SCRIPT_GRACE_PERIOD);
+        if self
+            .work_until_condition(|sim| sim.event_queue.peek().is_none(), timeout)
+            .is_none()
+        {
+            self.log("Script still running after grace period".to_string());
+        }
+    }
 }
 
 pub struct Handler;
 impl EventHandler for Handler {
     fn handle_event(&mut self, sim: &mut Simulation, event: Event) -> Result<(), Box<dyn Error>> {
-        if let Event::Command(command) = event {
+        if let Event::Command(command_entry) = event {
             let command = sim
                 .world
-                .query_one_mut::<&Box<dyn Command>>(command)
+                .query_one_mut::<&Box<dyn Command>>(command_entry)
                 .unwrap();
             let command: Box<dyn Command> = dyn_clone::clone_box(&**command);
+            if sim
+                .world
+                .get::<recorder::IssuedCommand>(command_entry)
+                .is_ok()
+            {
+                sim.record_command(&*command);
+            }
             command.execute(sim)?;
         }
         Ok(())
@@ -78,4 +125,86 @@ mod tests {
             .collect();
         assert_eq!(expected, actual);
     }
+
+    #[wasm_bindgen_test]
+    fn scripts_are_run_in_order_and_to_completion() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        sim.run_script(vec![
+            (OrderedFloat(5.), Box::new(TestCommand)),
+            (OrderedFloat(1.), Box::new(AddPeer(node1, node2))),
+            (OrderedFloat(2.), Box::new(RemovePeer(node1, node2))),
+            (OrderedFloat(3.), Box::new(AddPeer(node2, node1))),
+        ]);
+
+        assert_eq!(OrderedFloat(5.), sim.time.now());
+        assert!(sim.peers_mut(node1).is_empty());
+        assert!(sim.peers_mut(node2).contains(&node1));
+        assert_eq!(1, sim.world.query_mut::<&bool>().into_iter().count());
+    }
+
+    #[wasm_bindgen_test]
+    fn reset_clears_nodes_events_clock_and_log() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(5));
+        sim.do_in(OrderedFloat(50.), TestCommand);
+        sim.work_until(OrderedFloat(10.));
+        sim.log("something happened".to_string());
+
+        sim.do_now(Reset);
+        sim.process_next_event();
+
+        assert_eq!(0, sim.node_count());
+        assert_eq!(OrderedFloat(0.), sim.time.now());
+        assert!(sim.logger.entries().next().is_none());
+
+        // the previously scheduled command is gone, too
+        sim.work_until(OrderedFloat(100.));
+        assert!(sim.world.query_mut::<&bool>().into_iter().next().is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn work_until_targets_absolute_time_across_resets() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(5));
+        sim.work_until(OrderedFloat(10.));
+
+        sim.do_now(Reset);
+        sim.do_in(OrderedFloat(3.), SpawnRandomNodes(2));
+        sim.work_until(OrderedFloat(15.));
+
+        // the spawn scheduled before the reset was dropped
+        assert_eq!(0, sim.node_count());
+        assert_eq!(OrderedFloat(15.), sim.time.now());
+    }
+
+    #[wasm_bindgen_test]
+    fn resetting_while_catching_up_starts_over() {
+        let mut sim = Simulation::new();
+        sim.time.set_speed(1.);
+        let link_usage = sim.add_event_handler(LinkUsage::new(OrderedFloat(1000.)));
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.send_message(node1, node2, ()).unwrap();
+        sim.work_until(OrderedFloat(10.));
+        sim.start_recording();
+
+        sim.do_now(AddPeer(node1, node2));
+        sim.do_in(OrderedFloat(1.), Reset);
+        sim.catch_up(5.);
+
+        // one of the five real seconds was spent before the reset
+        assert_eq!(OrderedFloat(4.), sim.time.now());
+        let handlers = sim.additional_event_handlers();
+        let max_usage = handlers
+            .borrow()
+            .get::<LinkUsage>(link_usage)
+            .unwrap()
+            .max_usage(sim.time.now());
+        assert_eq!(0, max_usage);
+        let trace = sim.stop_recording().unwrap();
+        assert!(trace.commands.is_empty());
+    }
 }
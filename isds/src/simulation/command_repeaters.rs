@@ -102,6 +102,37 @@ impl Command for AtRandomIntervals {
     }
 }
 
+/// Marks a running background process, such as continuous mining. Self-rescheduling commands
+/// can check whether their process is still running before doing anything, see
+/// `Simulation::start_process`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Process(pub &'static str);
+
+impl Simulation {
+    /// Starts a process of the given kind, stopping any running process of the same kind first.
+    pub fn start_process(&mut self, kind: &'static str) -> Entity {
+        self.stop_process(kind);
+        self.world.spawn((Process(kind),))
+    }
+    /// Returns `false` if no process of the given kind was running.
+    pub fn stop_process(&mut self, kind: &'static str) -> bool {
+        let processes: Vec<Entity> = self
+            .world
+            .query::<&Process>()
+            .iter()
+            .filter(|(_, process)| process.0 == kind)
+            .map(|(entity, _)| entity)
+            .collect();
+        for &process in processes.iter() {
+            self.world.despawn(process).unwrap();
+        }
+        !processes.is_empty()
+    }
+    pub fn is_process_running(&self, process: Entity) -> bool {
+        self.world.contains(process)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
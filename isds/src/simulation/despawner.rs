@@ -6,8 +6,12 @@ impl EventHandler for Despawner {
     fn handle_event(&mut self, sim: &mut Simulation, event: Event) -> Result<(), Box<dyn Error>> {
         if let Event::Node(_, node_event) = event {
             match node_event {
-                NodeEvent::MessageArrived(message) => sim.world.despawn(message)?,
+                // the message might have been dropped before departing
+                NodeEvent::MessageArrived(message) if sim.world.contains(message) => {
+                    sim.world.despawn(message)?
+                }
                 NodeEvent::TimerFired(timer) => sim.world.despawn(timer)?,
+                NodeEvent::PeersAdded(added) => sim.world.despawn(added)?,
                 _ => (),
             }
         }
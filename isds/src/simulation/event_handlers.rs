@@ -2,6 +2,9 @@ use super::*;
 
 pub trait EventHandler: AsAny {
     fn handle_event(&mut self, sim: &mut Simulation, event: Event) -> Result<(), Box<dyn Error>>;
+    /// Called when the simulation is cleared (see `Simulation::clear`), so that handlers can
+    /// forget what they know about the old world.
+    fn reset(&mut self, _sim: &mut Simulation) {}
 }
 
 // we need this for enabling downcasting
@@ -18,22 +21,31 @@ impl<T: 'static> AsAny for T {
     }
 }
 
+/// Removed handlers leave a gap, so that the indices of the other handlers stay valid.
 #[derive(Default)]
-pub struct EventHandlers(Vec<Box<dyn EventHandler>>);
+pub struct EventHandlers(Vec<Option<Box<dyn EventHandler>>>);
 impl EventHandlers {
     pub fn new() -> Self {
         Self(Vec::new())
     }
     pub fn add(&mut self, event_handler: impl EventHandler + 'static) -> usize {
-        self.0.push(Box::new(event_handler));
+        self.0.push(Some(Box::new(event_handler)));
         self.0.len() - 1
     }
+    /// Returns `false` if there was no handler at the index (anymore).
+    pub fn remove(&mut self, handler_index: usize) -> bool {
+        self.0
+            .get_mut(handler_index)
+            .and_then(Option::take)
+            .is_some()
+    }
     pub fn get<T>(&self, handler_index: usize) -> Option<&T>
     where
         T: EventHandler,
     {
         self.0
             .get(handler_index)
+            .and_then(Option::as_ref)
             .and_then(|handler| (**handler).as_any().downcast_ref::<T>())
     }
     pub fn get_mut<T>(&mut self, handler_index: usize) -> Option<&mut T>
@@ -42,6 +54,7 @@ impl EventHandlers {
     {
         self.0
             .get_mut(handler_index)
+            .and_then(Option::as_mut)
             .and_then(|handler| (**handler).as_any_mut().downcast_mut::<T>())
     }
     pub(crate) fn handle_event(
@@ -49,11 +62,16 @@ impl EventHandlers {
         sim: &mut Simulation,
         event: Event,
     ) -> Result<(), Box<dyn Error>> {
-        for handler in self.0.iter_mut() {
+        for handler in self.0.iter_mut().flatten() {
             handler.handle_event(sim, event)?;
         }
         Ok(())
     }
+    pub(crate) fn reset(&mut self, sim: &mut Simulation) {
+        for handler in self.0.iter_mut().flatten() {
+            handler.reset(sim);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -92,4 +110,16 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[wasm_bindgen_test]
+    fn removing_keeps_other_indices() {
+        let mut handlers = EventHandlers::new();
+        let i = handlers.add(TestHandler(false));
+        let j = handlers.add(TestHandler(true));
+
+        assert!(handlers.remove(i));
+        assert!(!handlers.remove(i));
+        assert_eq!(None, handlers.get::<TestHandler>(i));
+        assert_eq!(Some(&TestHandler(true)), handlers.get::<TestHandler>(j));
+    }
 }
@@ -27,6 +27,12 @@ impl EventQueue {
     pub fn peek(&self) -> Option<(SimSeconds, Event)> {
         self.heap.peek().map(|te| (te.time_due, te.event))
     }
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
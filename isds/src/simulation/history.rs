@@ -0,0 +1,279 @@
+use super::*;
+
+/// How many edits can be undone at most.
+const MAX_UNDO_STEPS: usize = 100;
+
+/// An interactive change of the network topology that can be undone.
+#[derive(Debug, Clone)]
+pub enum TopologyEdit {
+    AddPeer(Entity, Entity),
+    RemovePeer(Entity, Entity),
+    /// Both directions at once, see `AddLink`.
+    AddLink(Entity, Entity),
+    /// Both directions at once, see `RemoveLink`.
+    RemoveLink(Entity, Entity),
+    SpawnNode(Entity, UnderlayNodeName, UnderlayPosition),
+}
+impl TopologyEdit {
+    fn replace_node(&mut self, old: Entity, new: Entity) {
+        let replace = |node: &mut Entity| {
+            if *node == old {
+                *node = new;
+            }
+        };
+        match self {
+            Self::AddPeer(node, peer)
+            | Self::RemovePeer(node, peer)
+            | Self::AddLink(node, peer)
+            | Self::RemoveLink(node, peer) => {
+                replace(node);
+                replace(peer);
+            }
+            Self::SpawnNode(node, _, _) => replace(node),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    undo_stack: VecDeque<TopologyEdit>,
+    redo_stack: Vec<TopologyEdit>,
+}
+impl EditHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+    fn push(&mut self, edit: TopologyEdit) {
+        self.redo_stack.clear();
+        self.undo_stack.push_back(edit);
+        if self.undo_stack.len() > MAX_UNDO_STEPS {
+            self.undo_stack.pop_front();
+        }
+    }
+    fn replace_node(&mut self, old: Entity, new: Entity) {
+        for edit in self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut()) {
+            edit.replace_node(old, new);
+        }
+    }
+    fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+}
+
+impl Simulation {
+    /// Records an edit (that has already been applied) so that it can be undone. Any undone edits
+    /// can't be redone afterwards.
+    pub fn record_edit(&mut self, edit: TopologyEdit) {
+        self.history.push(edit);
+    }
+    /// Records spawning an (already spawned) node.
+    pub fn record_spawned_node(&mut self, node: Entity) -> Result<(), hecs::ComponentError> {
+        let name = (*self.world.get::<UnderlayNodeName>(node)?).clone();
+        let position = *self.world.get::<UnderlayPosition>(node)?;
+        self.record_edit(TopologyEdit::SpawnNode(node, name, position));
+        Ok(())
+    }
+    pub fn history(&self) -> &EditHistory {
+        &self.history
+    }
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+    /// Reverts the most recent recorded edit. Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if let Some(edit) = self.history.undo_stack.pop_back() {
+            match edit {
+                TopologyEdit::AddPeer(node, peer) => self.remove_peer(node, peer),
+                TopologyEdit::RemovePeer(node, peer) => self.add_peer(node, peer),
+                TopologyEdit::AddLink(node, peer) => {
+                    self.remove_peer(node, peer);
+                    self.remove_peer(peer, node);
+                }
+                TopologyEdit::RemoveLink(node, peer) => {
+                    self.add_peer(node, peer);
+                    self.add_peer(peer, node);
+                }
+                TopologyEdit::SpawnNode(node, _, _) => {
+                    // later edits have been undone already, but protocols might have connected
+                    // the node since
+                    let linked_nodes: Vec<Entity> = self
+                        .world
+                        .query::<&PeerSet>()
+                        .iter()
+                        .filter(|(_, peers)| peers.contains(&node))
+                        .map(|(linked_node, _)| linked_node)
+                        .collect();
+                    for linked_node in linked_nodes {
+                        self.remove_peer(linked_node, node);
+                    }
+                    if self.world.despawn(node).is_err() {
+                        self.log(format!("Can't undo spawning {}", node.id()));
+                    }
+                    self.invalidate_spatial_index();
+                }
+            }
+            self.history.redo_stack.push(edit);
+            true
+        } else {
+            false
+        }
+    }
+    /// Re-applies the most recently undone edit. Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if let Some(edit) = self.history.redo_stack.pop() {
+            let edit = match edit {
+                TopologyEdit::AddPeer(node, peer) => {
+                    self.add_peer(node, peer);
+                    edit
+                }
+                TopologyEdit::RemovePeer(node, peer) => {
+                    self.remove_peer(node, peer);
+                    edit
+                }
+                TopologyEdit::AddLink(node, peer) => {
+                    self.add_peer(node, peer);
+                    self.add_peer(peer, node);
+                    edit
+                }
+                TopologyEdit::RemoveLink(node, peer) => {
+                    self.remove_peer(node, peer);
+                    self.remove_peer(peer, node);
+                    edit
+                }
+                TopologyEdit::SpawnNode(old_node, name, position) => {
+                    // we can't get the old entity back, so we update the references to it
+                    let node = self.world.spawn((name.clone(), position));
+                    self.invalidate_spatial_index();
+                    self.notify_node_spawned(node);
+                    self.history.replace_node(old_node, node);
+                    TopologyEdit::SpawnNode(node, name, position)
+                }
+            };
+            self.history.undo_stack.push_back(edit);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn peer_edits_can_be_undone_and_redone() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        sim.do_now(AddPeer(node1, node2));
+        sim.catch_up(1.);
+        sim.do_now(RemovePeer(node1, node2));
+        sim.catch_up(1.);
+        assert!(!sim.peers_mut(node1).contains(&node2));
+
+        assert!(sim.undo());
+        assert!(sim.peers_mut(node1).contains(&node2));
+        assert!(sim.undo());
+        assert!(!sim.peers_mut(node1).contains(&node2));
+        assert!(!sim.undo());
+
+        assert!(sim.redo());
+        assert!(sim.peers_mut(node1).contains(&node2));
+        assert!(sim.history().can_redo());
+    }
+
+    #[wasm_bindgen_test]
+    fn link_edits_are_undone_in_both_directions_at_once() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        sim.do_now(AddLink(node1, node2));
+        sim.catch_up(1.);
+        assert!(sim.peers_mut(node1).contains(&node2));
+        assert!(sim.peers_mut(node2).contains(&node1));
+
+        sim.do_now(RemoveLink(node2, node1));
+        sim.catch_up(1.);
+        assert!(sim.undo());
+        assert!(sim.peers_mut(node1).contains(&node2));
+        assert!(sim.peers_mut(node2).contains(&node1));
+
+        assert!(sim.undo());
+        assert!(!sim.peers_mut(node1).contains(&node2));
+        assert!(!sim.peers_mut(node2).contains(&node1));
+        assert!(!sim.undo());
+    }
+
+    #[wasm_bindgen_test]
+    fn redoing_a_spawn_updates_later_edits() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+
+        sim.do_now(SpawnRandomNodes(1));
+        sim.catch_up(1.);
+        let node2 = sim.all_other_nodes(node1)[0];
+        let node2_name = sim.name(node2);
+        sim.do_now(AddPeer(node1, node2));
+        sim.catch_up(1.);
+
+        assert!(sim.undo());
+        assert!(sim.undo());
+        assert_eq!(1, sim.node_count());
+
+        assert!(sim.redo());
+        assert!(sim.redo());
+        let new_node2 = sim.all_other_nodes(node1)[0];
+        assert_eq!(node2_name, sim.name(new_node2));
+        assert!(sim.peers_mut(node1).contains(&new_node2));
+    }
+
+    #[wasm_bindgen_test]
+    fn undoing_a_spawn_disconnects_the_node() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        sim.do_now(SpawnRandomNodes(1));
+        sim.catch_up(1.);
+        let node2 = sim.all_other_nodes(node1)[0];
+        // not recorded, as if a protocol had connected the nodes
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        sim.catch_up(1.);
+
+        assert!(sim.undo());
+        sim.catch_up(1.);
+        assert_eq!(1, sim.node_count());
+        assert!(sim
+            .world
+            .query::<&PeerSet>()
+            .iter()
+            .all(|(_, peers)| !peers.contains(&node2)));
+    }
+
+    #[wasm_bindgen_test]
+    fn new_edits_clear_redo_stack() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+
+        sim.do_now(AddPeer(node1, node2));
+        sim.catch_up(1.);
+        sim.undo();
+        sim.do_now(AddPeer(node2, node1));
+        sim.catch_up(1.);
+
+        assert!(!sim.redo());
+    }
+}
@@ -0,0 +1,99 @@
+use super::*;
+use std::collections::HashMap;
+
+/// Counts the messages sent along each (undirected) pair of nodes within the last `window` sim
+/// seconds, e.g., for visualizing which links carry the most traffic.
+pub struct LinkUsage {
+    window: SimSeconds,
+    sent: HashMap<(Entity, Entity), VecDeque<SimSeconds>>,
+}
+impl LinkUsage {
+    pub fn new(window: SimSeconds) -> Self {
+        Self {
+            window,
+            sent: HashMap::new(),
+        }
+    }
+    /// Number of messages sent between `node1` and `node2` (in either direction) within the
+    /// window before `now`.
+    pub fn usage(&self, node1: Entity, node2: Entity, now: SimSeconds) -> usize {
+        self.sent
+            .get(&Self::key(node1, node2))
+            .map_or(0, |times| self.count_recent(times, now))
+    }
+    /// The highest usage of any link within the window before `now`.
+    pub fn max_usage(&self, now: SimSeconds) -> usize {
+        self.sent
+            .values()
+            .map(|times| self.count_recent(times, now))
+            .max()
+            .unwrap_or(0)
+    }
+    fn count_recent(&self, times: &VecDeque<SimSeconds>, now: SimSeconds) -> usize {
+        times
+            .iter()
+            .filter(|&&time| time > now - self.window)
+            .count()
+    }
+    fn key(node1: Entity, node2: Entity) -> (Entity, Entity) {
+        (node1.min(node2), node1.max(node2))
+    }
+}
+impl EventHandler for LinkUsage {
+    fn handle_event(&mut self, sim: &mut Simulation, event: Event) -> Result<(), Box<dyn Error>> {
+        if let Event::Node(_, NodeEvent::MessageSent(message)) = event {
+            if let Ok(underlay_message) = sim.world.get::<UnderlayMessage>(message) {
+                let now = sim.time.now();
+                let times = self
+                    .sent
+                    .entry(Self::key(underlay_message.source, underlay_message.dest))
+                    .or_default();
+                times.push_back(now);
+                while times
+                    .front()
+                    .filter(|&&time| time <= now - self.window)
+                    .is_some()
+                {
+                    times.pop_front();
+                }
+            }
+        }
+        Ok(())
+    }
+    fn reset(&mut self, _: &mut Simulation) {
+        self.sent.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn usage_is_counted_per_link_in_both_directions() {
+        let mut sim = Simulation::new();
+        let link_usage = sim.add_event_handler(LinkUsage::new(OrderedFloat(10.)));
+
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        let node3 = sim.spawn_random_node();
+        sim.send_message(node1, node2, ()).unwrap();
+        sim.send_message(node2, node1, ()).unwrap();
+        sim.send_message(node1, node3, ()).unwrap();
+        sim.work_until(OrderedFloat(5.));
+
+        let handlers = sim.additional_event_handlers();
+        let handlers = handlers.borrow();
+        let link_usage = handlers.get::<LinkUsage>(link_usage).unwrap();
+        let now = sim.time.now();
+        assert_eq!(2, link_usage.usage(node1, node2, now));
+        assert_eq!(2, link_usage.usage(node2, node1, now));
+        assert_eq!(1, link_usage.usage(node3, node1, now));
+        assert_eq!(0, link_usage.usage(node2, node3, now));
+        assert_eq!(2, link_usage.max_usage(now));
+        assert_eq!(0, link_usage.max_usage(OrderedFloat(20.)));
+    }
+}
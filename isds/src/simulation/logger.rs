@@ -3,26 +3,242 @@ use super::*;
 // TODO perhaps make this use the real logger interface to be able to decouple Simulator from seed
 // one day?
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub time: SimSeconds,
+    /// The node (and its name) on whose behalf the message was logged, if any.
+    pub node: Option<(Entity, String)>,
+    pub message: String,
+}
+impl LogEntry {
+    pub fn to_json(&self) -> String {
+        let (node, node_name) = if let Some((node, node_name)) = &self.node {
+            (
+                node.id().to_string(),
+                format!("\"{}\"", json_escape(node_name)),
+            )
+        } else {
+            ("null".to_string(), "null".to_string())
+        };
+        format!(
+            "{{\"time\":{},\"node\":{},\"node_name\":{},\"message\":\"{}\"}}",
+            self.time,
+            node,
+            node_name,
+            json_escape(&self.message)
+        )
+    }
+}
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((_, node_name)) = &self.node {
+            write!(f, "{}: {}: {}", self.time, node_name, self.message)
+        } else {
+            write!(f, "{}: {}", self.time, self.message)
+        }
+    }
+}
+
+/// How much gets logged. Errors and warnings are always logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Leaves out routine events, such as mined blocks, e.g., for busy simulations.
+    Quiet,
+    Normal,
+    /// Also logs frequent low-level events, such as peer set changes.
+    Verbose,
+}
+
+/// How many of the most recent log entries the `Logger` keeps; older ones are dropped.
+pub const RETAINED_LOG_ENTRIES: usize = 12;
+
 pub struct Logger {
-    log: VecDeque<(SimSeconds, String)>,
+    log: VecDeque<LogEntry>,
+    /// All entries since the last `clear` (up to `export_limit`) for `to_json`, oldest first.
+    exported: VecDeque<LogEntry>,
+    export_limit: Option<usize>,
+    level: LogLevel,
 }
 impl Logger {
     pub fn new() -> Self {
         Self {
             log: VecDeque::new(),
+            exported: VecDeque::new(),
+            export_limit: None,
+            level: LogLevel::Normal,
+        }
+    }
+    pub fn level(&self) -> LogLevel {
+        self.level
+    }
+    pub fn set_level(&mut self, level: LogLevel) {
+        self.level = level;
+    }
+    /// Whether to also log frequent low-level events, such as peer set changes.
+    pub fn is_verbose(&self) -> bool {
+        self.level >= LogLevel::Verbose
+    }
+    /// Turning verbose logging off goes back to `LogLevel::Normal`, but keeps `LogLevel::Quiet`.
+    pub fn set_verbose(&mut self, verbose: bool) {
+        if verbose {
+            self.level = LogLevel::Verbose;
+        } else if self.level == LogLevel::Verbose {
+            self.level = LogLevel::Normal;
         }
     }
     pub fn log(&mut self, sim_time: SimSeconds, message: String) {
-        log!(format!("{}: {}", sim_time, message));
-        self.log.push_front((sim_time, message));
-        self.log.truncate(12);
+        self.push(LogEntry {
+            time: sim_time,
+            node: None,
+            message,
+        });
+    }
+    pub fn log_for_node(
+        &mut self,
+        sim_time: SimSeconds,
+        node: Entity,
+        node_name: String,
+        message: String,
+    ) {
+        self.push(LogEntry {
+            time: sim_time,
+            node: Some((node, node_name)),
+            message,
+        });
     }
-    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &(SimSeconds, String)> {
+    pub fn clear(&mut self) {
+        self.log.clear();
+        self.exported.clear();
+    }
+    /// Newest entries first.
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &LogEntry> {
         self.log.iter()
     }
+    /// Limits how many entries are kept for `to_json`, dropping the oldest ones first. Unlimited
+    /// by default.
+    pub fn set_export_limit(&mut self, export_limit: Option<usize>) {
+        self.export_limit = export_limit;
+        self.truncate_exported();
+    }
+    /// Returns all entries since the last `clear` (not just the `RETAINED_LOG_ENTRIES` most recent
+    /// ones, but at most the export limit) as a JSON array, oldest entries first.
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self.exported.iter().map(LogEntry::to_json).collect();
+        format!("[{}]", entries.join(","))
+    }
+    fn push(&mut self, entry: LogEntry) {
+        #[cfg(target_arch = "wasm32")]
+        log!(entry.to_string());
+        self.exported.push_back(entry.clone());
+        self.truncate_exported();
+        self.log.push_front(entry);
+        self.log.truncate(RETAINED_LOG_ENTRIES);
+    }
+    fn truncate_exported(&mut self) {
+        if let Some(export_limit) = self.export_limit {
+            let excess = self.exported.len().saturating_sub(export_limit);
+            self.exported.drain(..excess);
+        }
+    }
 }
 impl Default for Logger {
     fn default() -> Self {
         Self::new()
     }
 }
+
+impl Simulation {
+    pub fn set_verbose_logging(&mut self, verbose: bool) {
+        self.logger.set_verbose(verbose);
+    }
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        self.logger.set_level(level);
+    }
+    /// The whole log since the simulation was created or cleared as JSON, e.g., for offline
+    /// analysis, see `Logger::to_json`.
+    pub fn export_log_json(&self) -> String {
+        self.logger.to_json()
+    }
+    pub fn set_log_export_limit(&mut self, export_limit: Option<usize>) {
+        self.logger.set_export_limit(export_limit);
+    }
+}
+
+pub(super) fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn json_export_is_chronological_and_escaped() {
+        let mut logger = Logger::new();
+        logger.log(OrderedFloat(1.5), "first".to_string());
+        logger.log(OrderedFloat(2.), "\"second\"\n".to_string());
+
+        let expected = concat!(
+            "[{\"time\":1.5,\"node\":null,\"node_name\":null,\"message\":\"first\"},",
+            "{\"time\":2,\"node\":null,\"node_name\":null,\"message\":\"\\\"second\\\"\\n\"}]"
+        );
+        assert_eq!(expected, logger.to_json());
+    }
+
+    #[wasm_bindgen_test]
+    fn json_export_covers_more_than_retained_entries() {
+        let mut logger = Logger::new();
+        let count = RETAINED_LOG_ENTRIES + 8;
+        for i in 0..count {
+            logger.log(OrderedFloat(i as f64), i.to_string());
+        }
+        assert_eq!(RETAINED_LOG_ENTRIES, logger.entries().count());
+        assert!(logger.to_json().starts_with("[{\"time\":0,"));
+        assert_eq!(count, logger.to_json().matches("\"time\"").count());
+
+        logger.set_export_limit(Some(5));
+        assert_eq!(5, logger.to_json().matches("\"time\"").count());
+        assert!(logger
+            .to_json()
+            .starts_with(&format!("[{{\"time\":{},", count - 5)));
+
+        logger.clear();
+        assert_eq!("[]", logger.to_json());
+    }
+
+    #[wasm_bindgen_test]
+    fn disabling_verbose_logging_keeps_quiet_level() {
+        let mut logger = Logger::new();
+        logger.set_level(LogLevel::Quiet);
+        logger.set_verbose(false);
+        assert_eq!(LogLevel::Quiet, logger.level());
+
+        logger.set_verbose(true);
+        logger.set_verbose(false);
+        assert_eq!(LogLevel::Normal, logger.level());
+    }
+
+    #[wasm_bindgen_test]
+    fn node_interface_logs_contain_node() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        sim.node_interface(node).log("hello");
+
+        let entry = sim.logger.entries().next().unwrap().clone();
+        assert_eq!(Some((node, sim.name(node))), entry.node);
+        assert_eq!("hello", entry.message);
+    }
+}
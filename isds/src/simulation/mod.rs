@@ -1,42 +1,59 @@
 #![allow(clippy::enum_glob_use)]
 #![macro_use]
 extern crate gloo;
+// the browser console isn't available in native (headless) builds
+#[cfg(target_arch = "wasm32")]
 use gloo::console::log;
 
 pub use hecs::{Entity, World};
 pub use rand::prelude::{IteratorRandom, Rng, SliceRandom};
 pub use std::error::Error;
 
-use rand::rngs::ThreadRng;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::any::Any;
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
 
+mod bandwidth;
 mod command;
 mod command_repeaters;
 mod despawner;
 mod event_handlers;
 mod event_queue;
+mod history;
+mod link_usage;
 mod logger;
 mod node_interface;
 mod peers;
 mod protocol;
+mod recorder;
+mod request_reply;
+mod routing;
 mod shared;
+mod spatial_index;
 mod time;
 mod time_control;
 mod underlay;
 
 use despawner::Despawner;
 
-pub use command::{Command, EntityAction, ForSpecific};
-pub use command_repeaters::{AtRandomIntervals, AtStaticIntervals, MultipleTimes};
+pub use bandwidth::BandwidthMeter;
+pub use command::{Command, EntityAction, ForSpecific, Reset};
+pub use command_repeaters::{AtRandomIntervals, AtStaticIntervals, MultipleTimes, Process};
 pub use event_handlers::{EventHandler, EventHandlers};
 pub use event_queue::EventQueue;
-pub use logger::Logger;
+pub use history::{EditHistory, TopologyEdit};
+pub use link_usage::LinkUsage;
+pub use logger::{LogEntry, LogLevel, Logger, RETAINED_LOG_ENTRIES};
 pub use node_interface::{blockchain_types, NodeInterface};
 pub use protocol::{InvokeProtocolForAllNodes, Payload, PokeNode, PokeSpecificNode, Protocol};
+pub use recorder::CommandTrace;
+pub use request_reply::{Correlation, RequestId};
+pub use routing::{RelayedMessage, RoutingMetric};
 pub use shared::*;
+pub use spatial_index::SpatialIndex;
 pub use time::{OrderedFloat, RealSeconds, SimSeconds, Time, TimeSpan};
 pub use time_control::SlowDownOnMessages;
 
@@ -56,6 +73,15 @@ pub enum NodeEvent {
     MessageArrived(Entity),
     TimerFired(Entity),
     PeerSetChanged(PeerSetUpdate),
+    /// Many peers were added at once (e.g., when building a topology), see `AddedPeers`.
+    /// Protocols are notified about each of them as if it had been added on its own.
+    PeersAdded(Entity),
+    /// Handled right away (not scheduled) unless nodes are spawned while handling an event.
+    Spawned,
+    /// A transaction that was confirmed at the node got reverted by switching to another fork,
+    /// see `NodeInterface::report_reverted_transaction`. Not passed on to protocols, but event
+    /// handlers can react to it.
+    TransactionReverted(Entity),
     Poke,
 }
 
@@ -69,25 +95,60 @@ pub struct Simulation {
     #[readonly]
     pub logger: Logger,
 
+    default_genesis: Entity,
+
     additional_event_handlers: Rc<RefCell<EventHandlers>>,
     underlay_config: UnderlayConfig,
 
     event_queue: EventQueue,
-    rng: ThreadRng,
+    history: EditHistory,
+    spatial_index: Option<SpatialIndex>,
+    /// Per link, until when each of its lanes (up to the link capacity) is busy with a message.
+    link_busy_until: HashMap<(Entity, Entity), Vec<SimSeconds>>,
+    spawned_nodes: usize,
+    rng: StdRng,
+    seed: Option<u64>,
+    max_step: Option<SimSeconds>,
+    recording: Option<CommandTrace>,
+    /// Whether an event is being handled right now, see `recorder::IssuedCommand`.
+    handling_event: bool,
+    /// Whether `clear` was called while the event handlers couldn't be reset.
+    handlers_need_reset: bool,
 }
 impl Simulation {
     pub fn new() -> Self {
         Self::new_with_underlay_dimensions(800., 800.)
     }
     pub fn new_with_underlay_dimensions(width: f32, height: f32) -> Self {
+        let mut world = World::new();
+        let default_genesis = world.spawn((blockchain_types::GenesisBlock,));
         Self {
             time: Time::new(0.1),
-            world: World::new(),
+            world,
             logger: Logger::new(),
+            default_genesis,
             additional_event_handlers: Rc::new(RefCell::new(EventHandlers::new())),
             underlay_config: UnderlayConfig::new(width, height),
             event_queue: EventQueue::new(),
-            rng: rand::thread_rng(),
+            history: EditHistory::new(),
+            spatial_index: None,
+            link_busy_until: HashMap::new(),
+            spawned_nodes: 0,
+            rng: StdRng::from_entropy(),
+            seed: None,
+            max_step: None,
+            recording: None,
+            handling_event: false,
+            handlers_need_reset: false,
+        }
+    }
+    /// Like `new`, but all randomness (node positions, latencies, randomized commands, ...) is
+    /// drawn from a stream determined by `seed`, so runs can be reproduced.
+    pub fn new_with_seed(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            seed: Some(seed),
+            ..Self::new()
         }
     }
     /// Returns the index of the event handler, in case you want to modify it later.
@@ -96,6 +157,55 @@ impl Simulation {
             .borrow_mut()
             .add(event_handler)
     }
+    /// Detaches the event handler (e.g., a protocol) added under the given index. Returns `false`
+    /// if it was removed already. Must not be called by event handlers themselves.
+    pub fn remove_event_handler(&mut self, handler_index: usize) -> bool {
+        self.additional_event_handlers
+            .borrow_mut()
+            .remove(handler_index)
+    }
+    /// Despawns all entities, drops all scheduled events, clears the log and the edit history and
+    /// turns the clock back to zero (and node names start from `n0000` again). Event handlers (and
+    /// thus protocols) stay registered, but are reset (see `EventHandler::reset`). Seeded
+    /// simulations start over with the seed's random stream, and an ongoing recording starts
+    /// over, too.
+    pub fn clear(&mut self) {
+        self.world.clear();
+        self.default_genesis = self.spawn_genesis_block();
+        self.event_queue = EventQueue::new();
+        self.logger.clear();
+        self.history = EditHistory::new();
+        self.invalidate_spatial_index();
+        self.link_busy_until.clear();
+        self.spawned_nodes = 0;
+        self.time.reset();
+        if let Some(seed) = self.seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        if self.is_recording() {
+            self.start_recording();
+        }
+        let handlers = Rc::clone(&self.additional_event_handlers);
+        let reset_now = handlers
+            .try_borrow_mut()
+            .map(|mut handlers| handlers.reset(self));
+        // cleared by an event handler, which is still borrowed
+        self.handlers_need_reset = reset_now.is_err();
+    }
+    /// The simulation's random number generator. Randomized commands should draw from it rather
+    /// than creating their own, so that seeded simulations stay reproducible.
+    pub fn rng_mut(&mut self) -> &mut impl Rng {
+        &mut self.rng
+    }
+    /// Adds a component to an entity (or replaces the one it has), for code outside this module,
+    /// which can only read `world`.
+    pub fn insert_component(
+        &mut self,
+        entity: Entity,
+        component: impl hecs::Component,
+    ) -> Result<(), hecs::NoSuchEntity> {
+        self.world.insert_one(entity, component)
+    }
     pub fn additional_event_handlers(&self) -> Rc<RefCell<EventHandlers>> {
         Rc::clone(&self.additional_event_handlers)
     }
@@ -108,6 +218,8 @@ impl Simulation {
     pub fn schedule_at(&mut self, time_due: SimSeconds, event: Event) {
         self.event_queue.push(time_due, event);
     }
+    /// Processes all events due until `target_sim_time`. This is an absolute sim time, even if an
+    /// event turns the clock back (see `Reset`).
     pub fn work_until(&mut self, target_sim_time: SimSeconds) {
         while self
             .event_queue
@@ -119,29 +231,102 @@ impl Simulation {
         }
         self.time.advance_sim_time_to(target_sim_time);
     }
+    /// Like `work_until`, but processes at most `max_events` events so that the caller can yield
+    /// (e.g., to the browser) in between. Returns `true` if more events are due until
+    /// `target_sim_time`, in which case time is only advanced up to the last processed event.
+    pub fn work_until_with_budget(
+        &mut self,
+        target_sim_time: SimSeconds,
+        max_events: usize,
+    ) -> bool {
+        let mut processed_events = 0;
+        while self
+            .event_queue
+            .peek()
+            .filter(|&(time_due, _)| time_due <= target_sim_time)
+            .is_some()
+        {
+            if processed_events == max_events {
+                return true;
+            }
+            self.process_next_event();
+            processed_events += 1;
+        }
+        self.time.advance_sim_time_to(target_sim_time);
+        false
+    }
+    /// Processes events until `condition` holds or `timeout` sim seconds have passed. The
+    /// condition is only checked once all events due at the current time have been processed.
+    /// Returns the elapsed sim time if the condition was met in time. If an event turns the clock
+    /// back (see `Reset`), the time before that doesn't count.
+    pub fn work_until_condition(
+        &mut self,
+        condition: impl Fn(&Simulation) -> bool,
+        timeout: SimSeconds,
+    ) -> Option<SimSeconds> {
+        let mut start = self.time.now();
+        let mut deadline = start + timeout;
+        loop {
+            let next_event_due = self.event_queue.peek().map(|(time_due, _)| time_due);
+            if next_event_due != Some(self.time.now()) && condition(self) {
+                return Some(self.time.now() - start);
+            }
+            match next_event_due {
+                Some(time_due) if time_due <= deadline => {
+                    let rewind = self.process_next_event_measuring_rewind();
+                    start -= rewind;
+                    deadline -= rewind;
+                }
+                _ => {
+                    self.time.advance_sim_time_to(deadline);
+                    return None;
+                }
+            }
+        }
+    }
+    /// Limits how much sim time a single `catch_up` advances, so that long pauses between calls
+    /// (e.g., while the browser tab was in the background) don't lead to huge bursts of events.
+    /// Sim time that exceeds the limit is skipped, i.e., the simulation falls behind.
+    pub fn set_max_step(&mut self, max_step: Option<SimSeconds>) {
+        self.max_step = max_step;
+    }
+    pub fn max_step(&self) -> Option<SimSeconds> {
+        self.max_step
+    }
+    /// Advances sim time according to the elapsed real time (and the speed of time), but at most
+    /// by `max_step`. If an event handler pauses the clock, only the events due at the same time
+    /// are processed afterwards.
     pub fn catch_up(&mut self, elapsed_real_time: RealSeconds) {
         // a bit complicated because we need to account for the possibility that the speed of time
         // changes mid-way; otherwise like `work_until`
         let mut reference_sim_time = self.time.now();
         let mut remaining_real_time = elapsed_real_time;
         let mut last_speed = self.time.speed();
-        let mut target_sim_time = self.time.after(remaining_real_time);
+        let mut was_paused = self.time.paused();
+        let mut step_limit = self.max_step.map(|max_step| self.time.now() + max_step);
+        let capped = |target_sim_time: SimSeconds, step_limit: Option<SimSeconds>| {
+            step_limit.map_or(target_sim_time, |limit| target_sim_time.min(limit))
+        };
+        let mut target_sim_time = capped(self.time.after(remaining_real_time), step_limit);
 
-        while self
+        while let Some((time_due, _)) = self
             .event_queue
             .peek()
             .filter(|&(time_due, _)| time_due <= target_sim_time)
-            .is_some()
         {
-            self.process_next_event();
+            let rewind = self.process_next_event_measuring_rewind();
 
-            // the speed of time changed for some reason!
-            if self.time.speed() != last_speed {
-                remaining_real_time -=
-                    (self.time.now() - reference_sim_time).into_inner() / last_speed;
+            // the speed of time changed, the clock was paused or turned back for some reason!
+            if self.time.speed() != last_speed
+                || self.time.paused() != was_paused
+                || rewind > OrderedFloat(0.)
+            {
+                remaining_real_time -= (time_due - reference_sim_time).into_inner() / last_speed;
                 reference_sim_time = self.time.now();
                 last_speed = self.time.speed();
-                target_sim_time = self.time.after(remaining_real_time);
+                was_paused = self.time.paused();
+                step_limit = step_limit.map(|limit| limit - rewind);
+                target_sim_time = capped(self.time.after(remaining_real_time), step_limit);
             }
         }
         self.time.advance_sim_time_to(target_sim_time);
@@ -153,16 +338,54 @@ impl Simulation {
             self.log(format!("Error handling event: {}", e));
         }
     }
+    /// Like `process_next_event`, but returns how far an event handler turned the clock back
+    /// (e.g., by `Reset`), so that callers can move their targets accordingly.
+    fn process_next_event_measuring_rewind(&mut self) -> SimSeconds {
+        let (time_due, _) = self.event_queue.peek().unwrap();
+        self.process_next_event();
+        (time_due - self.time.now()).max(OrderedFloat(0.))
+    }
     fn handle_event(&mut self, event: Event) -> Result<(), Box<dyn Error>> {
+        let was_handling_event = std::mem::replace(&mut self.handling_event, true);
+        let result = self.dispatch_event(event);
+        self.handling_event = was_handling_event;
+        result
+    }
+    fn dispatch_event(&mut self, event: Event) -> Result<(), Box<dyn Error>> {
         command::Handler.handle_event(self, event)?;
 
-        Rc::clone(&self.additional_event_handlers)
-            .borrow_mut()
-            .handle_event(self, event)?;
+        let handlers = Rc::clone(&self.additional_event_handlers);
+        let result = handlers.borrow_mut().handle_event(self, event);
+        if std::mem::take(&mut self.handlers_need_reset) {
+            handlers.borrow_mut().reset(self);
+        }
+        result?;
 
         Despawner.handle_event(self, event)?;
         Ok(())
     }
+    /// Lets event handlers (and thus protocols) know about the node immediately, so that it is
+    /// fully set up once spawning returns.
+    pub(super) fn notify_node_spawned(&mut self, node: Entity) {
+        let event = Event::Node(node, NodeEvent::Spawned);
+        let handlers = Rc::clone(&self.additional_event_handlers);
+        let result = match handlers.try_borrow_mut() {
+            Ok(mut handlers) => {
+                let was_handling_event = std::mem::replace(&mut self.handling_event, true);
+                let result = handlers.handle_event(self, event);
+                self.handling_event = was_handling_event;
+                result
+            }
+            // spawned by an event handler
+            Err(_) => {
+                self.schedule_now(event);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            self.log(format!("Error handling event: {}", e));
+        }
+    }
     pub fn name(&self, entity: Entity) -> String {
         if let Ok(entity_ref) = self.world.entity(entity) {
             if let Some(node_name) = entity_ref.get::<UnderlayNodeName>() {
@@ -177,6 +400,11 @@ impl Simulation {
     pub fn log(&mut self, message: String) {
         self.logger.log(self.time.now(), message);
     }
+    pub fn log_for_node(&mut self, node: Entity, message: String) {
+        let node_name = self.name(node);
+        self.logger
+            .log_for_node(self.time.now(), node, node_name, message);
+    }
 }
 impl Default for Simulation {
     fn default() -> Self {
@@ -191,6 +419,62 @@ mod tests {
 
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
 
+    #[derive(Debug, Clone)]
+    struct RollDice;
+    impl Command for RollDice {
+        fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+            let roll = sim.rng_mut().gen_range(1..=6);
+            sim.log(format!("Rolled a {}", roll));
+            Ok(())
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn catching_up_is_limited_by_max_step() {
+        let mut sim = Simulation::new();
+        sim.set_max_step(Some(OrderedFloat(5.)));
+        for i in 1..=10 {
+            sim.do_at(OrderedFloat(i as f64), RollDice);
+        }
+
+        sim.catch_up(1e6);
+        assert_eq!(OrderedFloat(5.), sim.time.now());
+        assert_eq!(5, sim.logger.entries().count());
+
+        sim.catch_up(1e6);
+        assert_eq!(OrderedFloat(10.), sim.time.now());
+        assert_eq!(10, sim.logger.entries().count());
+
+        sim.set_max_step(None);
+        sim.catch_up(1e6);
+        assert!(sim.time.now() > OrderedFloat(1000.));
+    }
+
+    #[wasm_bindgen_test]
+    fn seeded_simulations_are_reproducible() {
+        let run = |seed| {
+            let mut sim = Simulation::new_with_seed(seed);
+            for _ in 0..20 {
+                sim.do_now(RollDice);
+            }
+            sim.do_now(SpawnRandomNodes(3));
+            sim.catch_up(1.);
+            let rolls: Vec<String> = sim
+                .logger
+                .entries()
+                .map(|entry| entry.message.clone())
+                .collect();
+            let positions: Vec<UnderlayPosition> = sim
+                .nodes()
+                .into_iter()
+                .map(|(_, _, position)| position)
+                .collect();
+            (rolls, positions)
+        };
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+
     #[wasm_bindgen_test]
     fn simultaneous_events_are_executed_in_order_of_scheduling() {
         let mut sim = Simulation::new();
@@ -224,4 +508,38 @@ mod tests {
         assert_eq!(event5, sim.event_queue.pop().unwrap().1);
         assert_eq!(event6, sim.event_queue.pop().unwrap().1);
     }
+
+    #[wasm_bindgen_test]
+    fn work_until_with_budget_stops_early_and_resumes() {
+        let mut sim = Simulation::new();
+        for i in 0..5 {
+            let event = Event::Generic(sim.world.spawn((i,)));
+            sim.schedule_at(OrderedFloat(1.), event);
+        }
+        let target_time = OrderedFloat(10.);
+
+        assert!(sim.work_until_with_budget(target_time, 3));
+        assert_eq!(OrderedFloat(1.), sim.time.now());
+
+        assert!(!sim.work_until_with_budget(target_time, 3));
+        assert_eq!(target_time, sim.time.now());
+        assert!(sim.event_queue.peek().is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn work_until_condition_stops_when_condition_holds() {
+        let mut sim = Simulation::new();
+        for i in 1..5 {
+            let event = Event::Generic(sim.world.spawn((i,)));
+            sim.schedule_at(OrderedFloat(i as f64), event);
+        }
+
+        let elapsed =
+            sim.work_until_condition(|sim| sim.time.now() >= OrderedFloat(3.), OrderedFloat(10.));
+        assert_eq!(Some(OrderedFloat(3.)), elapsed);
+
+        let elapsed = sim.work_until_condition(|_| false, OrderedFloat(10.));
+        assert_eq!(None, elapsed);
+        assert_eq!(OrderedFloat(13.), sim.time.now());
+    }
 }
@@ -1,4 +1,5 @@
 use hecs::QueryItem;
+use sha2::{Digest, Sha256};
 use std::collections::BTreeSet;
 
 use super::*;
@@ -8,10 +9,37 @@ pub struct BlockHeader {
     /// Substitute for the block's hash. We don't want to deal with the complexity of actual block
     /// hashes.
     pub id: Entity,
-    /// `None` only for first block.
+    /// `None` only for the first block after the genesis block.
     pub id_prev: Option<Entity>,
+    /// The genesis block this chain builds upon. `None` for the genesis block shared by all nodes
+    /// by default, see `Simulation::default_genesis_block`.
+    pub genesis: Option<Entity>,
     /// Not usually part of header but handy for us here.
     pub height: usize,
+    /// How much work mining the block took, in multiples of the minimum difficulty.
+    pub difficulty: u64,
+    /// The sum of the difficulties of all blocks in the chain up to and including this one. Like
+    /// `height`, not usually part of the header.
+    pub chainwork: u64,
+    /// The node that mined the block. Also not part of a real header.
+    pub miner: Entity,
+    /// When the block was mined.
+    pub timestamp: SimSeconds,
+}
+
+impl BlockHeader {
+    /// See `short_hash`.
+    pub fn short_id(&self) -> String {
+        short_hash(self.id)
+    }
+}
+
+/// A short, hash-like label for a block (or any other entity), for use in the UI and in exports.
+/// As blocks don't have real hashes, it is derived from the entity id: the first four bytes (in
+/// hex) of its SHA-256 hash.
+pub fn short_hash(id: Entity) -> String {
+    let hash = Sha256::digest(id.id().to_le_bytes());
+    hex::encode(&hash[..4])
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
@@ -43,6 +71,22 @@ impl IntoIterator for BlockContents {
     }
 }
 
+/// Marks an alternative genesis block. Blocks building upon different genesis blocks belong to
+/// different, incompatible blockchains.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GenesisBlock;
+
+impl Simulation {
+    pub fn spawn_genesis_block(&mut self) -> Entity {
+        self.world.spawn((GenesisBlock,))
+    }
+    /// The genesis block that all blockchains build upon unless they use another one (see
+    /// `spawn_genesis_block`). Blocks building upon it have no `BlockHeader::genesis`.
+    pub fn default_genesis_block(&self) -> Entity {
+        self.default_genesis
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Transaction {
     pub from: Address,
@@ -71,26 +115,60 @@ impl<'a> NodeInterface<'a> {
         self.sim.world.query_one_mut::<&Transaction>(tx_id).ok()
     }
     /// Registers a block in the global database, where it is immutable via the node interface.
-    /// Set `id_prev` to `None` if this will be the first block in a chain (after the virtual
-    /// genesis block).
+    /// Set `id_prev` to `None` if this will be the first block in a chain (after the genesis
+    /// block). The block has the same difficulty as the previous one (or `1`).
     pub fn spawn_block(
         &mut self,
         id_prev: Option<Entity>,
         contents: impl IntoIterator<Item = Entity>,
     ) -> BlockHeader {
-        let height = if let Some(id_prev) = id_prev {
-            self.get_block_header(id_prev)
-                .expect("No block exists at `id_prev`!")
-                .height
-                + 1
+        let difficulty = id_prev
+            .and_then(|id_prev| self.get_block_header(id_prev))
+            .map_or(1, |prev| prev.difficulty);
+        self.spawn_block_with_difficulty(id_prev, difficulty, contents)
+    }
+    /// Like `spawn_block`, but with an explicit difficulty.
+    pub fn spawn_block_with_difficulty(
+        &mut self,
+        id_prev: Option<Entity>,
+        difficulty: u64,
+        contents: impl IntoIterator<Item = Entity>,
+    ) -> BlockHeader {
+        if let Some(id_prev) = id_prev {
+            let prev = *self
+                .get_block_header(id_prev)
+                .expect("No block exists at `id_prev`!");
+            self.insert_block(Some(prev), prev.genesis, difficulty, contents)
         } else {
-            1
-        };
+            self.insert_block(None, None, difficulty, contents)
+        }
+    }
+    /// Like `spawn_block` for the first block in a chain, but building upon the given genesis
+    /// block (see `Simulation::spawn_genesis_block`) instead of the default one.
+    pub fn spawn_first_block(
+        &mut self,
+        genesis: Option<Entity>,
+        contents: impl IntoIterator<Item = Entity>,
+    ) -> BlockHeader {
+        self.insert_block(None, genesis, 1, contents)
+    }
+    fn insert_block(
+        &mut self,
+        prev: Option<BlockHeader>,
+        genesis: Option<Entity>,
+        difficulty: u64,
+        contents: impl IntoIterator<Item = Entity>,
+    ) -> BlockHeader {
         let id = self.sim.world.reserve_entity();
         let block_header = BlockHeader {
             id,
-            id_prev,
-            height,
+            id_prev: prev.map(|prev| prev.id),
+            genesis,
+            height: prev.map_or(0, |prev| prev.height) + 1,
+            difficulty,
+            chainwork: prev.map_or(0, |prev| prev.chainwork) + difficulty,
+            miner: self.node,
+            timestamp: self.sim.time.now(),
         };
         let block_contents: BlockContents = contents.into_iter().collect();
         self.sim
@@ -99,6 +177,51 @@ impl<'a> NodeInterface<'a> {
             .unwrap();
         block_header
     }
+    /// Registers an externally defined block in the global database, e.g., for replaying a
+    /// recorded chain. `header.id` must be a freshly reserved entity, other entities (such as
+    /// nodes or messages) are rejected. Importing the very same block again is fine.
+    pub fn import_block(
+        &mut self,
+        header: BlockHeader,
+        contents: BlockContents,
+    ) -> Result<(), String> {
+        if let Some(existing_header) = self.get_block_header(header.id) {
+            return if *existing_header == header {
+                Ok(())
+            } else {
+                Err(format!("Another block exists at {}", header.id.id()))
+            };
+        }
+        if matches!(self.sim.world.entity(header.id), Ok(entity) if !entity.is_empty()) {
+            return Err(format!("Entity {} is already in use", header.id.id()));
+        }
+        if let Some(id_prev) = header.id_prev {
+            if let Some(prev) = self.get_block_header(id_prev) {
+                if prev.height + 1 != header.height || prev.genesis != header.genesis {
+                    return Err("Block doesn't match its parent".to_string());
+                }
+                if prev.chainwork + header.difficulty != header.chainwork {
+                    return Err("Block's chainwork doesn't match its parent's".to_string());
+                }
+            }
+        } else if header.height != 1 {
+            return Err("First block must have a height of 1".to_string());
+        } else if header.chainwork != header.difficulty {
+            return Err("First block's chainwork must equal its difficulty".to_string());
+        }
+        self.sim
+            .world
+            .insert(header.id, (header, contents))
+            .map_err(|_| format!("Can't insert block at {}", header.id.id()))
+    }
+    /// Lets event handlers know that the transaction was reverted at this node after having been
+    /// confirmed, see `NodeEvent::TransactionReverted`.
+    pub fn report_reverted_transaction(&mut self, tx_id: Entity) {
+        self.sim.schedule_now(Event::Node(
+            self.node,
+            NodeEvent::TransactionReverted(tx_id),
+        ));
+    }
     pub fn get_block(
         &mut self,
         block_id: Entity,
@@ -133,6 +256,68 @@ mod tests {
         }
     }
 
+    #[wasm_bindgen_test]
+    fn imported_blocks_with_forged_chainwork_are_rejected() {
+        let mut sim = Simulation::new();
+        let node_id = sim.spawn_random_node();
+        let mut node = sim.node_interface(node_id);
+        let first_block = node.spawn_block(None, vec![]);
+
+        let forged_first_block = BlockHeader {
+            id: node.sim.world.reserve_entity(),
+            chainwork: 1000,
+            ..first_block
+        };
+        assert!(node
+            .import_block(forged_first_block, BlockContents::new())
+            .is_err());
+
+        let honest_block = BlockHeader {
+            id: node.sim.world.reserve_entity(),
+            id_prev: Some(first_block.id),
+            height: 2,
+            chainwork: 2,
+            ..first_block
+        };
+        let forged_block = BlockHeader {
+            id: node.sim.world.reserve_entity(),
+            chainwork: 1000,
+            ..honest_block
+        };
+        assert!(node
+            .import_block(forged_block, BlockContents::new())
+            .is_err());
+        assert_eq!(None, node.get_block_header(forged_block.id));
+        assert_eq!(
+            Ok(()),
+            node.import_block(honest_block, BlockContents::new())
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn imported_blocks_must_not_overwrite_other_entities() {
+        let mut sim = Simulation::new();
+        let node_id = sim.spawn_random_node();
+        let mut node = sim.node_interface(node_id);
+        let first_block = node.spawn_block(None, vec![]);
+
+        let block_at_node = BlockHeader {
+            id: node_id,
+            ..first_block
+        };
+        assert!(node
+            .import_block(block_at_node, BlockContents::new())
+            .is_err());
+        assert_eq!(None, node.get_block_header(node_id));
+        assert!(node.sim.world.get::<UnderlayNodeName>(node_id).is_ok());
+
+        let block = BlockHeader {
+            id: node.sim.world.reserve_entity(),
+            ..first_block
+        };
+        assert_eq!(Ok(()), node.import_block(block, BlockContents::new()));
+    }
+
     #[wasm_bindgen_test]
     fn transactions_are_spawned_and_gettable() {
         let mut sim = Simulation::new();
@@ -194,6 +379,36 @@ mod tests {
         assert_eq!(2, block_2_header.height);
     }
 
+    #[wasm_bindgen_test]
+    fn spawn_block_inherits_genesis() {
+        let mut sim = Simulation::new();
+        let genesis = sim.spawn_genesis_block();
+        let node_id = sim.spawn_random_node();
+        let mut node = sim.node_interface(node_id);
+
+        let block_1_header = node.spawn_first_block(Some(genesis), []);
+        let block_2_header = node.spawn_block(Some(block_1_header.id), []);
+        let other_block_header = node.spawn_block(None, []);
+
+        assert_eq!(Some(genesis), block_2_header.genesis);
+        assert_eq!(1, block_1_header.height);
+        assert_eq!(None, other_block_header.genesis);
+    }
+
+    #[wasm_bindgen_test]
+    fn short_hashes_differ() {
+        let mut sim = Simulation::new();
+        let node_id = sim.spawn_random_node();
+        let mut node = sim.node_interface(node_id);
+
+        let block_1_header = node.spawn_block(None, []);
+        let block_2_header = node.spawn_block(Some(block_1_header.id), []);
+
+        assert_eq!(8, block_1_header.short_id().len());
+        assert_eq!(short_hash(block_1_header.id), block_1_header.short_id());
+        assert_ne!(block_1_header.short_id(), block_2_header.short_id());
+    }
+
     #[wasm_bindgen_test]
     fn block_contents_are_spawned_and_gettable() {
         let mut sim = Simulation::new();
@@ -12,17 +12,39 @@ impl<'a> NodeInterface<'a> {
     pub fn new(sim: &'a mut Simulation, node: Entity) -> Self {
         Self { sim, node }
     }
+    /// Returns the node's `T` component, inserting `T::default()` first if the node doesn't have
+    /// one yet. Use `try_get` for probing optional state.
     pub fn get<T: Payload + Default>(&mut self) -> QueryItem<&mut T> {
         if self.sim.world.query_one_mut::<&T>(self.node).is_err() {
             self.sim.world.insert_one(self.node, T::default()).unwrap();
         }
         self.sim.world.query_one_mut::<&mut T>(self.node).unwrap()
     }
+    /// Like `get`, but for components that need the simulation to be constructed.
+    pub fn get_or_insert_with<T: Payload>(
+        &mut self,
+        init: impl FnOnce(&Simulation) -> T,
+    ) -> QueryItem<'_, &mut T> {
+        if self.sim.world.query_one_mut::<&T>(self.node).is_err() {
+            let component = init(self.sim);
+            self.sim.world.insert_one(self.node, component).unwrap();
+        }
+        self.sim.world.query_one_mut::<&mut T>(self.node).unwrap()
+    }
+    /// Returns the node's `T` component if it has one. Never inserts anything.
+    pub fn try_get<T: Payload>(&mut self) -> Option<&T> {
+        self.sim.world.query_one_mut::<&T>(self.node).ok()
+    }
     pub fn log(&mut self, message: &str) {
-        self.sim
-            .log(format!("{}: {}", self.sim.name(self.node), message));
+        self.sim.log_for_node(self.node, message.to_string());
     }
-    pub fn send_message<P: Payload>(&mut self, dest: Entity, payload: P) -> Entity {
+    /// Only logs if the simulation's log level is at least `level`.
+    pub fn log_at(&mut self, level: LogLevel, message: &str) {
+        if self.sim.logger.level() >= level {
+            self.log(message);
+        }
+    }
+    pub fn send_message<P: Payload>(&mut self, dest: Entity, payload: P) -> Result<Entity, String> {
         let source = self.node;
         self.sim.send_message(source, dest, payload)
     }
@@ -30,11 +52,70 @@ impl<'a> NodeInterface<'a> {
         &mut self,
         dest: Entity,
         payloads: impl IntoIterator<Item = P>,
-    ) -> Vec<Entity> {
+    ) -> Result<Vec<Entity>, String> {
         let source = self.node;
         self.sim.send_messages(source, dest, payloads)
     }
+    /// See `Simulation::send_message_to_random_peer`.
+    pub fn send_message_to_random_peer<P: Payload>(
+        &mut self,
+        payload: P,
+    ) -> Result<Entity, String> {
+        let source = self.node;
+        self.sim.send_message_to_random_peer(source, payload)
+    }
+    pub fn send_request<P: Payload>(&mut self, dest: Entity, payload: P) -> Result<Entity, String> {
+        let source = self.node;
+        self.sim.send_request(source, dest, payload)
+    }
+    pub fn send_reply<P: Payload>(
+        &mut self,
+        request: &UnderlayMessage,
+        payload: P,
+    ) -> Result<Entity, String> {
+        self.sim.send_reply(request, payload)
+    }
+    pub fn send_message_routed<P: Payload>(
+        &mut self,
+        dest: Entity,
+        payload: P,
+        metric: RoutingMetric,
+    ) -> Result<Vec<Entity>, String> {
+        let source = self.node;
+        self.sim.send_message_routed(source, dest, payload, metric)
+    }
+    /// Schedules a timer that fires at this node after `delay`, carrying `payload`. Protocols get
+    /// it back via `Protocol::handle_timer` if `payload` is of their `MessagePayload` type.
+    pub fn schedule_self<P: Payload>(&mut self, delay: SimSeconds, payload: P) -> Entity {
+        let timer = self.sim.world.spawn((payload,));
+        self.sim
+            .schedule_in(delay, Event::Node(self.node, NodeEvent::TimerFired(timer)));
+        timer
+    }
+    pub fn now(&self) -> SimSeconds {
+        self.sim.time.now()
+    }
     pub fn rng(&mut self) -> &mut impl Rng {
         &mut self.sim.rng
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn try_get_does_not_insert_components() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+
+        assert!(sim.node_interface(node).try_get::<PeerSet>().is_none());
+        assert!(sim.world.get::<PeerSet>(node).is_err());
+
+        sim.node_interface(node).get::<PeerSet>();
+        assert!(sim.node_interface(node).try_get::<PeerSet>().is_some());
+    }
+}
@@ -1,7 +1,9 @@
 use super::*;
 
+use rand::distributions::WeightedIndex;
+use rand_distr::Distribution;
 use std::cmp;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// The parameter of `NodeEvent::PeerSetChanged`
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -14,6 +16,9 @@ pub enum PeerSetUpdate {
 pub struct AddPeer(pub Entity, pub Entity);
 impl Command for AddPeer {
     fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        if !sim.peers_mut(self.0).contains(&self.1) {
+            sim.record_edit(TopologyEdit::AddPeer(self.0, self.1));
+        }
         sim.add_peer(self.0, self.1);
         Ok(())
     }
@@ -23,11 +28,86 @@ impl Command for AddPeer {
 pub struct RemovePeer(pub Entity, pub Entity);
 impl Command for RemovePeer {
     fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        if sim.peers_mut(self.0).contains(&self.1) {
+            sim.record_edit(TopologyEdit::RemovePeer(self.0, self.1));
+        }
         sim.remove_peer(self.0, self.1);
         Ok(())
     }
 }
 
+/// Connects two nodes in both directions, recorded as a single edit, e.g., for a click on a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddLink(pub Entity, pub Entity);
+impl Command for AddLink {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        if !sim.peers_mut(self.0).contains(&self.1) || !sim.peers_mut(self.1).contains(&self.0) {
+            sim.record_edit(TopologyEdit::AddLink(self.0, self.1));
+        }
+        sim.add_peer(self.0, self.1);
+        sim.add_peer(self.1, self.0);
+        Ok(())
+    }
+}
+
+/// Disconnects two nodes in both directions, recorded as a single edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoveLink(pub Entity, pub Entity);
+impl Command for RemoveLink {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        if sim.peers_mut(self.0).contains(&self.1) || sim.peers_mut(self.1).contains(&self.0) {
+            sim.record_edit(TopologyEdit::RemoveLink(self.0, self.1));
+        }
+        sim.remove_peer(self.0, self.1);
+        sim.remove_peer(self.1, self.0);
+        Ok(())
+    }
+}
+
+/// Connects `node` (in both directions) with all other nodes within `radius`, e.g., for modeling
+/// range-limited wireless links.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectWithinRadius {
+    pub node: Entity,
+    pub radius: f32,
+}
+impl Command for ConnectWithinRadius {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.connect_within_radius(self.node, self.radius)?;
+        Ok(())
+    }
+}
+
+/// Connects `node` (in both directions) with `target_degree` random nodes it isn't connected to
+/// yet, e.g., after spawning it. Protocols then sync the node as usual for added peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinNetwork {
+    pub node: Entity,
+    pub target_degree: usize,
+    /// Pick nodes with a probability proportional to their number of peers (plus one), a bit like
+    /// a node that learns about other nodes via address gossip would.
+    pub prefer_well_connected: bool,
+}
+impl Command for JoinNetwork {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.join_network(self.node, self.target_degree, self.prefer_well_connected);
+        Ok(())
+    }
+}
+
+/// Cuts the victim off from all its peers and connects it to the attackers only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EclipseNode {
+    pub victim: Entity,
+    pub attackers: Vec<Entity>,
+}
+impl Command for EclipseNode {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.eclipse_node(self.victim, &self.attackers);
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct MakeDelaunayNetwork;
 impl Command for MakeDelaunayNetwork {
@@ -37,6 +117,16 @@ impl Command for MakeDelaunayNetwork {
     }
 }
 
+/// Connects every pair of nodes bidirectionally.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MakeFullMesh;
+impl Command for MakeFullMesh {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.make_full_mesh();
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct PeerSet {
     peers: BTreeSet<Entity>,
@@ -81,6 +171,10 @@ impl PeerSet {
     pub fn last_update(&self) -> SimSeconds {
         self.last_update
     }
+    /// Signals a change that affects how the peer links are drawn, e.g., a moved node.
+    pub fn touch(&mut self, now: SimSeconds) {
+        self.last_update = now;
+    }
 }
 impl IntoIterator for PeerSet {
     type Item = Entity;
@@ -91,7 +185,12 @@ impl IntoIterator for PeerSet {
     }
 }
 
+/// The peers that were added to a node at once, see `NodeEvent::PeersAdded`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AddedPeers(pub Vec<Entity>);
+
 impl Simulation {
+    /// Inserts an empty `PeerSet` if the node doesn't have one yet.
     pub fn peers_mut(&mut self, node: Entity) -> hecs::RefMut<PeerSet> {
         if self.world.get_mut::<PeerSet>(node).is_err() {
             let peers = PeerSet::default();
@@ -101,20 +200,132 @@ impl Simulation {
     }
     pub fn add_peer(&mut self, node: Entity, peer: Entity) {
         let now = self.time.now();
-        self.peers_mut(node).insert(peer, now);
+        let is_new_peer = self.peers_mut(node).insert(peer, now);
+        if is_new_peer && self.logger.is_verbose() {
+            let message = format!("Connected to {}.", self.name(peer));
+            self.log_for_node(node, message);
+        }
         self.schedule_now(Event::Node(
             node,
             NodeEvent::PeerSetChanged(PeerSetUpdate::PeerAdded(peer)),
         ));
     }
+    /// Also drops messages to `peer` that were queued but didn't depart yet. Logs a warning if
+    /// `node` ends up without any peers.
     pub fn remove_peer(&mut self, node: Entity, peer: Entity) {
         let now = self.time.now();
-        self.peers_mut(node).remove(&peer, now);
+        let mut peers = self.peers_mut(node);
+        let was_peer = peers.remove(&peer, now);
+        let became_isolated = was_peer && peers.is_empty();
+        drop(peers);
+        if was_peer && self.logger.is_verbose() {
+            let message = format!("Disconnected from {}.", self.name(peer));
+            self.log_for_node(node, message);
+        }
+        if became_isolated {
+            self.log_for_node(node, "Warning: node has no peers left".to_string());
+        }
+        self.drop_queued_messages(node, peer);
         self.schedule_now(Event::Node(
             node,
             NodeEvent::PeerSetChanged(PeerSetUpdate::PeerRemoved(peer)),
         ));
     }
+    /// Nodes without any peers, i.e., nodes that can't send anything to anyone.
+    pub fn isolated_nodes(&self) -> Vec<Entity> {
+        self.world
+            .query::<Option<&PeerSet>>()
+            .with::<UnderlayNodeName>()
+            .iter()
+            .filter(|(_, peers)| peers.is_none_or(PeerSet::is_empty))
+            .map(|(node, _)| node)
+            .collect()
+    }
+    /// Adds all other nodes within `radius` of `node` as peers, and `node` as their peer.
+    pub fn connect_within_radius(&mut self, node: Entity, radius: f32) -> Result<(), String> {
+        let position = *self
+            .world
+            .get::<UnderlayPosition>(node)
+            .map_err(|_| format!("Node ({}) has no position", node.id()))?;
+        let neighbors: Vec<Entity> = self
+            .nodes_within_radius(position, radius)
+            .into_iter()
+            .filter(|&other| other != node)
+            .collect();
+        for neighbor in neighbors {
+            if !self.peers_mut(node).contains(&neighbor) {
+                self.add_peer(node, neighbor);
+            }
+            if !self.peers_mut(neighbor).contains(&node) {
+                self.add_peer(neighbor, node);
+            }
+        }
+        Ok(())
+    }
+    /// See `JoinNetwork`. Returns the new peers, which are fewer than `target_degree` if there
+    /// aren't enough other nodes.
+    pub fn join_network(
+        &mut self,
+        node: Entity,
+        target_degree: usize,
+        prefer_well_connected: bool,
+    ) -> Vec<Entity> {
+        let mut candidates: Vec<Entity> = self
+            .all_other_nodes(node)
+            .into_iter()
+            .filter(|other| !self.peers_mut(node).contains(other))
+            .collect();
+        let mut new_peers = vec![];
+        while new_peers.len() < target_degree && !candidates.is_empty() {
+            let weights = candidates.iter().map(|&candidate| {
+                if prefer_well_connected {
+                    self.world.get::<PeerSet>(candidate).map_or(0, |p| p.len()) + 1
+                } else {
+                    1
+                }
+            });
+            let distribution = WeightedIndex::new(weights).unwrap();
+            new_peers.push(candidates.swap_remove(distribution.sample(&mut self.rng)));
+        }
+        for &peer in new_peers.iter() {
+            self.add_peer(node, peer);
+            if !self.peers_mut(peer).contains(&node) {
+                self.add_peer(peer, node);
+            }
+        }
+        new_peers
+    }
+    /// Removes all (bidirectional) links of `victim` to nodes other than the attackers and links it
+    /// (bidirectionally) to all attackers.
+    pub fn eclipse_node(&mut self, victim: Entity, attackers: &[Entity]) {
+        let honest_peers: Vec<Entity> = self
+            .peers_mut(victim)
+            .iter()
+            .copied()
+            .filter(|peer| !attackers.contains(peer))
+            .collect();
+        for peer in honest_peers {
+            self.remove_peer(victim, peer);
+        }
+        let honest_nodes_with_victim_as_peer: Vec<Entity> = self
+            .world
+            .query::<&PeerSet>()
+            .iter()
+            .filter(|(node, peers)| peers.contains(&victim) && !attackers.contains(node))
+            .map(|(node, _)| node)
+            .collect();
+        for node in honest_nodes_with_victim_as_peer {
+            self.remove_peer(node, victim);
+        }
+        for &attacker in attackers {
+            if !self.peers_mut(victim).contains(&attacker) {
+                self.add_peer(victim, attacker);
+            }
+            if !self.peers_mut(attacker).contains(&victim) {
+                self.add_peer(attacker, victim);
+            }
+        }
+    }
     pub fn add_random_nodes_as_peers(
         &mut self,
         node: Entity,
@@ -134,6 +345,76 @@ impl Simulation {
             self.add_peer(node, peer);
         }
     }
+    /// Picks one of the node's peers uniformly at random, using the simulation's random number
+    /// generator. Returns `None` if the node has no peers.
+    pub fn pick_random_peer(&mut self, node: Entity) -> Option<Entity> {
+        let peers = self.world.get::<PeerSet>(node).ok()?;
+        peers.iter().choose(&mut self.rng).copied()
+    }
+    /// Like `send_message`, but to a random peer of `source` rather than to a given node. Fails if
+    /// `source` has no peers.
+    pub fn send_message_to_random_peer<P: Payload>(
+        &mut self,
+        source: Entity,
+        payload: P,
+    ) -> Result<Entity, String> {
+        let dest = self
+            .pick_random_peer(source)
+            .ok_or("Couldn't find a suitable message destination. Not enough peers?")?;
+        self.send_message(source, dest, payload)
+    }
+    /// Like calling `add_peer` for each pair, but only schedules a single `PeersAdded` event per
+    /// affected node instead of one event per pair. Pairs of nodes that are already peers are
+    /// skipped.
+    pub fn add_peers_batched(&mut self, pairs: impl IntoIterator<Item = (Entity, Entity)>) {
+        let now = self.time.now();
+        let mut added_peers: BTreeMap<Entity, Vec<Entity>> = BTreeMap::new();
+        for (node, peer) in pairs {
+            if self.peers_mut(node).insert(peer, now) {
+                if self.logger.is_verbose() {
+                    let message = format!("Connected to {}.", self.name(peer));
+                    self.log_for_node(node, message);
+                }
+                added_peers.entry(node).or_default().push(peer);
+            }
+        }
+        for (node, peers) in added_peers {
+            let added = self.world.spawn((AddedPeers(peers),));
+            self.schedule_now(Event::Node(node, NodeEvent::PeersAdded(added)));
+        }
+    }
+
+    /// Connects the nodes of each pair (looked up by name) bidirectionally, e.g., for reproducing
+    /// a published network graph. Nodes that don't exist yet are spawned at random positions.
+    pub fn load_topology(&mut self, edges: &[(String, String)]) {
+        let mut pairs = vec![];
+        for (name1, name2) in edges.iter().filter(|(name1, name2)| name1 != name2) {
+            let node1 = self.find_or_spawn_node(name1);
+            let node2 = self.find_or_spawn_node(name2);
+            pairs.push((node1, node2));
+            pairs.push((node2, node1));
+        }
+        self.add_peers_batched(pairs);
+    }
+    fn find_or_spawn_node(&mut self, name: &str) -> Entity {
+        self.find_node_by_name(name).unwrap_or_else(|| {
+            let node = self.spawn_named_node(name);
+            self.record_spawned_node(node).unwrap();
+            node
+        })
+    }
+
+    fn make_full_mesh(&mut self) {
+        let nodes = self.all_nodes();
+        // batched, so that even large meshes only schedule one event per node
+        let pairs = nodes.iter().flat_map(|&node1| {
+            nodes
+                .iter()
+                .filter(move |&&node2| node2 != node1)
+                .map(move |&node2| (node1, node2))
+        });
+        self.add_peers_batched(pairs.collect::<Vec<_>>());
+    }
 
     fn make_delaunay_network(&mut self) {
         use delaunator::{triangulate, Point};
@@ -156,17 +437,33 @@ impl Simulation {
         }
         let triangles = triangulate(&points).triangles;
         assert!(triangles.len() % 3 == 0);
-        for i in (0..triangles.len()).step_by(3) {
-            let node1 = nodes[triangles[i]];
-            let node2 = nodes[triangles[i + 1]];
-            let node3 = nodes[triangles[i + 2]];
-            self.add_peer(node1, node2);
-            self.add_peer(node1, node3);
-            self.add_peer(node2, node1);
-            self.add_peer(node2, node3);
-            self.add_peer(node3, node1);
-            self.add_peer(node3, node2);
+        if triangles.is_empty() && nodes.len() > 1 {
+            // fewer than three nodes or all of them on a line
+            self.log("Warning: No triangulation exists, connecting nodes in a line".to_string());
+            let mut order: Vec<usize> = (0..nodes.len()).collect();
+            order.sort_by_key(|&i| (OrderedFloat(points[i].x), OrderedFloat(points[i].y)));
+            let pairs = order.windows(2).flat_map(|neighbors| {
+                let node1 = nodes[neighbors[0]];
+                let node2 = nodes[neighbors[1]];
+                [(node1, node2), (node2, node1)]
+            });
+            self.add_peers_batched(pairs);
+            return;
         }
+        let pairs = triangles.chunks(3).flat_map(|triangle| {
+            let node1 = nodes[triangle[0]];
+            let node2 = nodes[triangle[1]];
+            let node3 = nodes[triangle[2]];
+            [
+                (node1, node2),
+                (node1, node3),
+                (node2, node1),
+                (node2, node3),
+                (node3, node1),
+                (node3, node2),
+            ]
+        });
+        self.add_peers_batched(pairs);
     }
 }
 
@@ -193,6 +490,299 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[derive(Debug, Default, Clone)]
+    struct AnnouncedPeers(Vec<Entity>);
+
+    struct RememberAddedPeers;
+    impl Protocol for RememberAddedPeers {
+        type MessagePayload = ();
+        fn handle_message(
+            &self,
+            _: NodeInterface,
+            _: UnderlayMessage,
+            _: (),
+        ) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+        fn handle_peer_set_update(
+            &self,
+            mut node: NodeInterface,
+            update: PeerSetUpdate,
+        ) -> Result<(), Box<dyn Error>> {
+            if let PeerSetUpdate::PeerAdded(peer) = update {
+                node.get::<AnnouncedPeers>().0.push(peer);
+            }
+            Ok(())
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn batched_peers_are_announced_only_if_new() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(RememberAddedPeers));
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        let node3 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.catch_up(1.);
+        sim.set_verbose_logging(true);
+
+        sim.add_peers_batched(vec![(node1, node2), (node1, node3)]);
+        sim.catch_up(1.);
+
+        let announced = sim.world.get::<AnnouncedPeers>(node1).unwrap().0.clone();
+        assert_eq!(vec![node2, node3], announced);
+        let connections: Vec<(Option<Entity>, String)> = sim
+            .logger
+            .entries()
+            .filter(|entry| entry.message.starts_with("Connected to"))
+            .map(|entry| {
+                (
+                    entry.node.as_ref().map(|(node, _)| *node),
+                    entry.message.clone(),
+                )
+            })
+            .collect();
+        let expected = format!("Connected to {}.", sim.name(node3));
+        assert_eq!(vec![(Some(node1), expected)], connections);
+        assert!(sim.world.query::<&AddedPeers>().iter().next().is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn messages_to_random_peers_go_to_peers() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(10));
+        sim.catch_up(1.);
+        let nodes = sim.all_nodes();
+        let node = nodes[0];
+        assert!(sim.send_message_to_random_peer(node, ()).is_err());
+
+        for &peer in nodes[1..4].iter() {
+            sim.add_peer(node, peer);
+        }
+        for _ in 0..50 {
+            let message = sim.send_message_to_random_peer(node, ()).unwrap();
+            let dest = sim.world.get::<UnderlayMessage>(message).unwrap().dest;
+            assert!(nodes[1..4].contains(&dest));
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn loaded_topology_connects_named_nodes() {
+        let mut sim = Simulation::new();
+        let node_a = sim.spawn_random_node();
+        sim.world
+            .insert_one(node_a, UnderlayNodeName("a".to_string()))
+            .unwrap();
+        let edges = [("a", "b"), ("b", "c"), ("c", "a")]
+            .map(|(name1, name2)| (name1.to_string(), name2.to_string()));
+        sim.load_topology(&edges);
+
+        assert_eq!(3, sim.node_count());
+        let node_b = sim.find_node_by_name("b").unwrap();
+        let node_c = sim.find_node_by_name("c").unwrap();
+        for (node, expected) in [
+            (node_a, [node_b, node_c]),
+            (node_b, [node_a, node_c]),
+            (node_c, [node_a, node_b]),
+        ] {
+            assert_eq!(BTreeSet::from(expected), sim.peers_mut(node).peers);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn loaded_nodes_are_named_when_spawned() {
+        struct SpawnedNames(Rc<RefCell<Vec<String>>>);
+        impl EventHandler for SpawnedNames {
+            fn handle_event(
+                &mut self,
+                sim: &mut Simulation,
+                event: Event,
+            ) -> Result<(), Box<dyn Error>> {
+                if let Event::Node(node, NodeEvent::Spawned) = event {
+                    self.0.borrow_mut().push(sim.name(node));
+                }
+                Ok(())
+            }
+        }
+
+        let mut sim = Simulation::new();
+        let names = Rc::new(RefCell::new(vec![]));
+        sim.add_event_handler(SpawnedNames(Rc::clone(&names)));
+        sim.load_topology(&[("a".to_string(), "b".to_string())]);
+        assert_eq!(vec!["a", "b"], *names.borrow());
+
+        // spawning the nodes can be undone
+        assert!(sim.undo());
+        assert!(sim.undo());
+        assert_eq!(0, sim.node_count());
+    }
+
+    #[wasm_bindgen_test]
+    fn full_mesh_connects_all_pairs() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(5));
+        sim.catch_up(1.);
+        assert!(sim.event_queue.is_empty());
+
+        sim.do_now(MakeFullMesh);
+        sim.process_next_event();
+        // batched: one event per node rather than one per added peer
+        assert_eq!(5, sim.event_queue.len());
+        sim.catch_up(1.);
+
+        let degrees: Vec<usize> = sim
+            .world
+            .query::<&PeerSet>()
+            .iter()
+            .map(|(_, peers)| peers.len())
+            .collect();
+        assert_eq!(vec![4; 5], degrees);
+    }
+
+    #[wasm_bindgen_test]
+    fn delaunay_network_schedules_one_event_per_node() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(500));
+        sim.catch_up(1.);
+        assert!(sim.event_queue.is_empty());
+
+        sim.make_delaunay_network();
+
+        // one event per added peer before batching
+        let added_peers: usize = sim
+            .world
+            .query::<&PeerSet>()
+            .iter()
+            .map(|(_, peers)| peers.len())
+            .sum();
+        assert!(added_peers > 2 * 500);
+        assert_eq!(500, sim.event_queue.len());
+        assert!(sim.isolated_nodes().is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn delaunay_network_handles_degenerate_inputs() {
+        let mut sim = Simulation::new();
+        sim.make_delaunay_network();
+        assert_eq!(0, sim.node_count());
+
+        let node1 = sim.spawn_random_node_at_position(10., 10.);
+        sim.make_delaunay_network();
+        assert!(sim.peers_mut(node1).is_empty());
+
+        let node3 = sim.spawn_random_node_at_position(30., 30.);
+        sim.make_delaunay_network();
+        assert!(sim.peers_mut(node1).contains(&node3));
+        assert!(sim.peers_mut(node3).contains(&node1));
+
+        let node2 = sim.spawn_random_node_at_position(20., 20.);
+        sim.make_delaunay_network();
+        let peers = |sim: &mut Simulation, node| -> Vec<Entity> {
+            sim.peers_mut(node).iter().copied().collect()
+        };
+        assert_eq!(vec![node2], peers(&mut sim, node1));
+        assert_eq!(2, peers(&mut sim, node2).len());
+        assert_eq!(vec![node2], peers(&mut sim, node3));
+    }
+
+    #[wasm_bindgen_test]
+    fn isolated_nodes_are_detected_and_reported() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        let node3 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        sim.add_peer(node2, node3);
+
+        assert_eq!(vec![node3], sim.isolated_nodes());
+
+        sim.remove_peer(node2, node1);
+        assert!(sim.logger.entries().next().is_none());
+
+        sim.remove_peer(node1, node2);
+        let mut isolated_nodes = sim.isolated_nodes();
+        isolated_nodes.sort();
+        assert_eq!(vec![node1, node3], isolated_nodes);
+        assert_eq!(
+            Some((node1, sim.name(node1))),
+            sim.logger.entries().next().unwrap().node
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn connect_within_radius_connects_only_close_nodes() {
+        let mut sim = Simulation::new();
+        let center = sim.spawn_random_node_at_position(100., 100.);
+        let close = sim.spawn_random_node_at_position(130., 140.); // distance 50
+        let edge = sim.spawn_random_node_at_position(100., 160.); // distance 60
+        let far = sim.spawn_random_node_at_position(200., 200.);
+
+        sim.do_now(ConnectWithinRadius {
+            node: center,
+            radius: 60.,
+        });
+        sim.catch_up(10.);
+
+        let peers: Vec<Entity> = sim.peers_mut(center).iter().copied().collect();
+        let mut expected = vec![close, edge];
+        expected.sort();
+        assert_eq!(expected, peers);
+        assert!(sim.peers_mut(close).contains(&center));
+        assert!(sim.peers_mut(edge).contains(&center));
+        assert!(sim.peers_mut(far).is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn peer_changes_are_only_logged_if_verbose() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.add_peer(node1, node2);
+        sim.add_peer(node2, node1);
+        assert!(sim.logger.entries().next().is_none());
+
+        sim.set_verbose_logging(true);
+        sim.remove_peer(node1, node2);
+        sim.remove_peer(node1, node2); // no change
+
+        let expected = LogEntry {
+            time: sim.time.now(),
+            node: Some((node1, sim.name(node1))),
+            message: format!("Disconnected from {}.", sim.name(node2)),
+        };
+        assert_eq!(1, sim.logger.entries().filter(|&e| *e == expected).count());
+    }
+
+    #[wasm_bindgen_test]
+    fn eclipsed_node_is_connected_to_attackers_only() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(10));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+
+        let nodes = sim.all_nodes();
+        let victim = nodes[0];
+        let attackers = vec![nodes[1], nodes[2]];
+        sim.do_now(EclipseNode {
+            victim,
+            attackers: attackers.clone(),
+        });
+        sim.catch_up(1.);
+
+        let mut expected = attackers.clone();
+        expected.sort();
+        let actual: Vec<Entity> = sim.peers_mut(victim).iter().copied().collect();
+        assert_eq!(expected, actual);
+        for &node in nodes[3..].iter() {
+            assert!(!sim.peers_mut(node).contains(&victim));
+        }
+        for &attacker in attackers.iter() {
+            assert!(sim.peers_mut(attacker).contains(&victim));
+        }
+    }
+
     #[wasm_bindgen_test]
     fn add_random_other_nodes_as_peers_adds_peers() {
         let mut sim = Simulation::new();
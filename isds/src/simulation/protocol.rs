@@ -34,6 +34,29 @@ pub trait Protocol: 'static {
         message_payload: Self::MessagePayload,
     ) -> Result<(), Box<dyn Error>>;
 
+    /// What to do once we got a reply to a request sent via `NodeInterface::send_request`.
+    /// Handled like any other message by default.
+    fn handle_reply(
+        &self,
+        node: NodeInterface,
+        underlay_message: UnderlayMessage,
+        message_payload: Self::MessagePayload,
+        _round_trip_time: SimSeconds,
+    ) -> Result<(), Box<dyn Error>> {
+        self.handle_message(node, underlay_message, message_payload)
+    }
+
+    /// Lets protocols set up a node's state right when it is spawned rather than lazily.
+    fn on_node_spawned(&self, _node: NodeInterface) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// A short, human-readable description of the node's protocol state, e.g., for tooltips.
+    /// Empty by default.
+    fn node_status(&self, _node: NodeInterface) -> String {
+        String::new()
+    }
+
     /// A default action to take on user interaction with the node (such as a click).
     fn handle_poke(&self, mut node: NodeInterface) -> Result<(), Box<dyn Error>> {
         node.log("I just got poked!");
@@ -49,6 +72,16 @@ pub trait Protocol: 'static {
     ) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
+
+    /// What to do once a timer scheduled via `NodeInterface::schedule_self` fires. Optional
+    /// because not every protocol needs to schedule its own work.
+    fn handle_timer(
+        &self,
+        _node: NodeInterface,
+        _timer_payload: Self::MessagePayload,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
 }
 
 impl Simulation {
@@ -70,6 +103,10 @@ impl<P: Protocol> InvokeProtocolForAllNodes<P> {
                 // I probably either know about this already or it's not my business
             }
             NodeEvent::MessageArrived(message) => {
+                if sim.world.get::<RelayedMessage>(message).is_ok() {
+                    // only an intermediate leg of a routed message, not my business yet
+                    return Ok(());
+                }
                 if let Ok((underlay_message, payload)) = sim
                     .world
                     .query_one_mut::<(&UnderlayMessage, &P::MessagePayload)>(message)
@@ -80,18 +117,52 @@ impl<P: Protocol> InvokeProtocolForAllNodes<P> {
                     //     sim.name(node),
                     //     sim.name(underlay_message.source),
                     // ));
-                    self.0
-                        .handle_message(sim.node_interface(node), underlay_message, payload)?;
+                    if let Some(Correlation::Reply(request_id)) = underlay_message.correlation {
+                        let round_trip_time = sim.time.now() - request_id.sent_at;
+                        self.0.handle_reply(
+                            sim.node_interface(node),
+                            underlay_message,
+                            payload,
+                            round_trip_time,
+                        )?;
+                    } else {
+                        self.0.handle_message(
+                            sim.node_interface(node),
+                            underlay_message,
+                            payload,
+                        )?;
+                    }
                 }
                 // not my message payload, not my business
             }
-            NodeEvent::TimerFired(_) => {
-                todo!();
+            NodeEvent::TimerFired(timer) => {
+                if let Ok(payload) = sim.world.query_one_mut::<&P::MessagePayload>(timer) {
+                    let payload = payload.clone();
+                    self.0.handle_timer(sim.node_interface(node), payload)?;
+                }
+                // not my timer, not my business
             }
             NodeEvent::PeerSetChanged(update) => {
                 self.0
                     .handle_peer_set_update(sim.node_interface(node), update)?;
             }
+            NodeEvent::PeersAdded(added) => {
+                let peers = sim
+                    .world
+                    .get::<AddedPeers>(added)
+                    .map(|added| added.0.clone())
+                    .unwrap_or_default();
+                for peer in peers {
+                    self.0.handle_peer_set_update(
+                        sim.node_interface(node),
+                        PeerSetUpdate::PeerAdded(peer),
+                    )?;
+                }
+            }
+            NodeEvent::Spawned => {
+                self.0.on_node_spawned(sim.node_interface(node))?;
+            }
+            NodeEvent::TransactionReverted(_) => {}
             NodeEvent::Poke => {
                 // sim.log(format!("{}: Got poked!", sim.name(node)));
                 self.0.handle_poke(sim.node_interface(node))?;
@@ -119,6 +190,61 @@ mod tests {
 
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
 
+    #[derive(Debug, Default, Clone)]
+    struct HeartbeatState {
+        beats: Vec<(SimSeconds, u32)>,
+    }
+
+    struct Heartbeat;
+    impl Protocol for Heartbeat {
+        type MessagePayload = u32;
+
+        fn handle_message(
+            &self,
+            _node: NodeInterface,
+            _underlay_message: UnderlayMessage,
+            _message_payload: Self::MessagePayload,
+        ) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+        fn handle_poke(&self, mut node: NodeInterface) -> Result<(), Box<dyn Error>> {
+            node.schedule_self(OrderedFloat(5.), 1_u32);
+            Ok(())
+        }
+        fn handle_timer(
+            &self,
+            mut node: NodeInterface,
+            beat: Self::MessagePayload,
+        ) -> Result<(), Box<dyn Error>> {
+            let now = node.now();
+            node.get::<HeartbeatState>().beats.push((now, beat));
+            if beat < 3 {
+                node.schedule_self(OrderedFloat(5.), beat + 1);
+            }
+            Ok(())
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn timers_fire_at_the_scheduled_time() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(Heartbeat));
+        let node = sim.spawn_random_node();
+        sim.work_until(OrderedFloat(10.));
+        sim.do_now(PokeSpecificNode(node));
+        sim.work_until(OrderedFloat(100.));
+
+        let state = sim.world.get::<HeartbeatState>(node).unwrap();
+        assert_eq!(
+            vec![
+                (OrderedFloat(15.), 1),
+                (OrderedFloat(20.), 2),
+                (OrderedFloat(25.), 3)
+            ],
+            state.beats
+        );
+    }
+
     #[wasm_bindgen_test]
     fn invoking_two_protocols_for_all_nodes_is_possible() {
         let mut sim = Simulation::new();
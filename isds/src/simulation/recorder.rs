@@ -0,0 +1,178 @@
+use super::logger::json_escape;
+use super::*;
+
+/// The commands issued to a simulation from the outside (e.g., by the user clicking around), with
+/// the sim time at which they were executed; see `Simulation::start_recording`. Commands issued by
+/// other commands (such as repetitions) aren't recorded, as replaying the issuing command brings
+/// them back anyway.
+#[derive(Debug, Clone, Default)]
+pub struct CommandTrace {
+    /// The seed of the recorded simulation, if it was seeded (see `Simulation::new_with_seed`).
+    pub seed: Option<u64>,
+    pub commands: Vec<(SimSeconds, Box<dyn Command>)>,
+}
+impl CommandTrace {
+    /// The seed and the commands (in their debug representation) as JSON, e.g., for attaching to
+    /// bug reports. It's meant for humans and can't be parsed back into commands.
+    pub fn to_json(&self) -> String {
+        let commands: Vec<String> = self
+            .commands
+            .iter()
+            .map(|(time, command)| {
+                format!(
+                    "{{\"time\":{},\"command\":\"{}\"}}",
+                    time,
+                    json_escape(&format!("{:?}", command))
+                )
+            })
+            .collect();
+        let seed = self
+            .seed
+            .map_or("null".to_string(), |seed| seed.to_string());
+        format!(
+            "{{\"seed\":{},\"commands\":[{}]}}",
+            seed,
+            commands.join(",")
+        )
+    }
+}
+
+/// Marks scheduled commands that weren't issued while handling an event.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct IssuedCommand;
+
+impl Simulation {
+    /// Starts recording issued commands, dropping any previous recording. For a trace that
+    /// replays exactly, start recording right after creating a seeded simulation.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(CommandTrace {
+            seed: self.seed,
+            commands: vec![],
+        });
+    }
+    pub fn stop_recording(&mut self) -> Option<CommandTrace> {
+        self.recording.take()
+    }
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+    /// Schedules the recorded commands at the times they were executed. The simulation should be
+    /// set up like the recorded one, i.e., fresh, with the same seed and event handlers.
+    pub fn replay(&mut self, trace: &CommandTrace) {
+        for (time, command) in trace.commands.iter() {
+            self.do_at_boxed(*time, dyn_clone::clone_box(&**command));
+        }
+    }
+    /// Turns a seeded simulation that has been recorded since its creation back to the `target`
+    /// sim time by clearing it and replaying the recorded commands up to `target`. Recording goes
+    /// on, but commands recorded after `target` are dropped. Event handlers are reset along with
+    /// the simulation (see `clear`) and then see the replayed events again.
+    pub fn rewind_to(&mut self, target: SimSeconds) -> Result<(), String> {
+        let trace = self.recording.take().ok_or("Nothing was recorded")?;
+        if trace.seed.is_none() {
+            self.recording = Some(trace);
+            return Err("Only seeded simulations can be rewound".to_string());
+        }
+        self.clear();
+        self.start_recording();
+        for (time, command) in trace.commands.into_iter() {
+            if time <= target {
+                self.do_at_boxed(time, command);
+            }
+        }
+        self.work_until(target);
+        Ok(())
+    }
+    pub(super) fn record_command(&mut self, command: &(dyn Command + 'static)) {
+        let now = self.time.now();
+        if let Some(recording) = self.recording.as_mut() {
+            recording
+                .commands
+                .push((now, dyn_clone::clone_box(command)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    fn topology(sim: &mut Simulation) -> Vec<(String, (f32, f32), Vec<String>)> {
+        let mut topology: Vec<(String, (f32, f32), Vec<String>)> = sim
+            .nodes()
+            .into_iter()
+            .map(|(node, name, position)| {
+                let peers: Vec<Entity> = sim.peers_mut(node).iter().copied().collect();
+                let mut peer_names: Vec<String> =
+                    peers.into_iter().map(|peer| sim.name(peer)).collect();
+                peer_names.sort();
+                (name, (position.x, position.y), peer_names)
+            })
+            .collect();
+        topology.sort_by(|a, b| a.0.cmp(&b.0));
+        topology
+    }
+
+    #[wasm_bindgen_test]
+    fn replaying_a_recording_leads_to_the_same_state() {
+        let mut sim = Simulation::new_with_seed(23);
+        sim.start_recording();
+        sim.do_now(SpawnRandomNodes(10));
+        sim.catch_up(1.);
+        sim.do_now(MakeDelaunayNetwork);
+        sim.do_in(OrderedFloat(5.), ShufflePositions);
+        sim.do_in(
+            OrderedFloat(8.),
+            AtStaticIntervals::new(SpawnRandomNodes(1), OrderedFloat(4.)),
+        );
+        sim.work_until(OrderedFloat(20.));
+        let trace = sim.stop_recording().unwrap();
+        assert!(!sim.is_recording());
+        // the repetitions aren't recorded
+        assert_eq!(4, trace.commands.len());
+        assert!(trace.to_json().starts_with("{\"seed\":23,"));
+
+        let mut replayed = Simulation::new_with_seed(trace.seed.unwrap());
+        replayed.replay(&trace);
+        replayed.work_until(OrderedFloat(20.));
+
+        // the repetitions skip their first run, so nodes are spawned at 12.1 and 16.1
+        assert_eq!(12, replayed.node_count());
+        assert_eq!(topology(&mut sim), topology(&mut replayed));
+    }
+
+    #[wasm_bindgen_test]
+    fn rewinding_leads_to_the_earlier_state() {
+        let mut sim = Simulation::new_with_seed(42);
+        sim.start_recording();
+        sim.do_now(SpawnRandomNodes(10));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.do_in(OrderedFloat(5.), ShufflePositions);
+        sim.do_in(
+            OrderedFloat(8.),
+            AtStaticIntervals::new(SpawnRandomNodes(1), OrderedFloat(4.)),
+        );
+        sim.work_until(OrderedFloat(12.));
+        let trace = sim.recording.clone().unwrap();
+        sim.do_in(OrderedFloat(1.), ShufflePositions);
+        sim.work_until(OrderedFloat(20.));
+
+        let mut fresh = Simulation::new_with_seed(42);
+        fresh.replay(&trace);
+        fresh.work_until(OrderedFloat(12.));
+
+        sim.rewind_to(OrderedFloat(12.)).unwrap();
+        assert_eq!(OrderedFloat(12.), sim.time.now());
+        // the repetitions skip their first run, so only the node spawned at 12 is new
+        assert_eq!(11, sim.node_count());
+        assert_eq!(topology(&mut fresh), topology(&mut sim));
+        // the shuffle after the target time was dropped from the recording
+        assert_eq!(4, sim.stop_recording().unwrap().commands.len());
+
+        assert!(sim.rewind_to(OrderedFloat(0.)).is_err());
+        assert!(Simulation::new().rewind_to(OrderedFloat(0.)).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use super::*;
+
+/// Identifies a request so that replies can be matched to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestId {
+    /// The message entity of the request. Note that it's despawned once the request arrived.
+    pub message: Entity,
+    pub sent_at: SimSeconds,
+}
+
+/// Optional part of `UnderlayMessage` for request/response protocols.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Correlation {
+    Request(RequestId),
+    Reply(RequestId),
+}
+
+impl Simulation {
+    /// Like `send_message`, but the receiver can answer via `send_reply`. Returns the request
+    /// message entity.
+    pub fn send_request<P: Payload>(
+        &mut self,
+        source: Entity,
+        dest: Entity,
+        payload: P,
+    ) -> Result<Entity, String> {
+        let message = self.send_message(source, dest, payload)?;
+        let request_id = RequestId {
+            message,
+            sent_at: self.time.now(),
+        };
+        self.set_correlation(message, Correlation::Request(request_id));
+        Ok(message)
+    }
+    /// Answers `request` (which must have been sent via `send_request`) by sending `payload`
+    /// back to its source.
+    pub fn send_reply<P: Payload>(
+        &mut self,
+        request: &UnderlayMessage,
+        payload: P,
+    ) -> Result<Entity, String> {
+        let request_id = match request.correlation {
+            Some(Correlation::Request(request_id)) => request_id,
+            _ => return Err("Can only reply to requests".to_string()),
+        };
+        let message = self.send_message(request.dest, request.source, payload)?;
+        self.set_correlation(message, Correlation::Reply(request_id));
+        Ok(message)
+    }
+    fn set_correlation(&mut self, message: Entity, correlation: Correlation) {
+        self.world
+            .get_mut::<UnderlayMessage>(message)
+            .unwrap()
+            .correlation = Some(correlation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[derive(Debug, Default, Clone)]
+    struct PingState {
+        round_trip_times: Vec<SimSeconds>,
+    }
+
+    struct Ping;
+    impl Protocol for Ping {
+        type MessagePayload = ();
+
+        fn handle_message(
+            &self,
+            mut node: NodeInterface,
+            underlay_message: UnderlayMessage,
+            _message_payload: Self::MessagePayload,
+        ) -> Result<(), Box<dyn Error>> {
+            node.send_reply(&underlay_message, ())?;
+            Ok(())
+        }
+        fn handle_reply(
+            &self,
+            mut node: NodeInterface,
+            _underlay_message: UnderlayMessage,
+            _message_payload: Self::MessagePayload,
+            round_trip_time: SimSeconds,
+        ) -> Result<(), Box<dyn Error>> {
+            node.get::<PingState>()
+                .round_trip_times
+                .push(round_trip_time);
+            Ok(())
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn round_trip_time_is_sum_of_flight_times() {
+        let mut sim = Simulation::new();
+        sim.add_event_handler(InvokeProtocolForAllNodes(Ping));
+        let node1 = sim.spawn_random_node_at_position(0., 0.);
+        let node2 = sim.spawn_random_node_at_position(300., 400.);
+        sim.work_until(OrderedFloat(1.));
+
+        let request = sim.send_request(node1, node2, ()).unwrap();
+        let request_flight_time = {
+            let time_span = sim.world.get::<TimeSpan>(request).unwrap();
+            (time_span.end - time_span.start).into_inner()
+        };
+        sim.work_until(OrderedFloat(10.));
+
+        // both legs have the same length
+        let state = sim.world.get::<PingState>(node1).unwrap();
+        assert_eq!(1, state.round_trip_times.len());
+        let round_trip_time = state.round_trip_times[0].into_inner();
+        assert!((round_trip_time - 2. * request_flight_time).abs() < 1e-9);
+    }
+
+    #[wasm_bindgen_test]
+    fn replying_to_plain_messages_fails() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        let message = sim.send_message(node1, node2, ()).unwrap();
+        let underlay_message = *sim.world.get::<UnderlayMessage>(message).unwrap();
+
+        assert!(sim.send_reply(&underlay_message, ()).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use super::*;
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// How the length of a path through the peer-to-peer network is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingMetric {
+    Hops,
+    Latency,
+}
+
+/// Marks the legs of a routed message that end at a relaying node instead of the final
+/// destination. Protocols don't get to see such legs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayedMessage;
+
+impl Simulation {
+    /// Returns the shortest path from `source` to `dest` along (directed) peer links, including
+    /// both `source` and `dest`.
+    pub fn shortest_path(
+        &self,
+        source: Entity,
+        dest: Entity,
+        metric: RoutingMetric,
+    ) -> Option<Vec<Entity>> {
+        let mut distances: HashMap<Entity, OrderedFloat<f32>> = HashMap::new();
+        let mut predecessors: HashMap<Entity, Entity> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+
+        distances.insert(source, OrderedFloat(0.));
+        queue.push(Reverse((OrderedFloat(0.), source)));
+
+        while let Some(Reverse((distance, node))) = queue.pop() {
+            if node == dest {
+                break;
+            }
+            if distances.get(&node).is_some_and(|&d| distance > d) {
+                continue; // we already found a shorter path to this node
+            }
+            let peers = match self.world.get::<PeerSet>(node) {
+                Ok(peers) => peers,
+                Err(_) => continue,
+            };
+            for &peer in peers.iter() {
+                let link_length = match metric {
+                    RoutingMetric::Hops => 1.,
+                    RoutingMetric::Latency => {
+                        match UnderlayLine::from_nodes(&self.world, node, peer) {
+                            Ok(line) => line.length(),
+                            Err(_) => continue,
+                        }
+                    }
+                };
+                let new_distance = distance + link_length;
+                if distances.get(&peer).is_none_or(|&d| new_distance < d) {
+                    distances.insert(peer, new_distance);
+                    predecessors.insert(peer, node);
+                    queue.push(Reverse((new_distance, peer)));
+                }
+            }
+        }
+
+        if !distances.contains_key(&dest) {
+            return None;
+        }
+        let mut path = vec![dest];
+        while let Some(&predecessor) = predecessors.get(path.last().unwrap()) {
+            path.push(predecessor);
+        }
+        path.reverse();
+        Some(path)
+    }
+    /// Sends a message hop by hop along the shortest path through the peer-to-peer network, as
+    /// opposed to sending it directly. Returns the message entities of all legs, in order.
+    pub fn send_message_routed<P: Payload>(
+        &mut self,
+        source: Entity,
+        dest: Entity,
+        payload: P,
+        metric: RoutingMetric,
+    ) -> Result<Vec<Entity>, String> {
+        let path = self
+            .shortest_path(source, dest, metric)
+            .filter(|path| path.len() >= 2)
+            .ok_or_else(|| format!("No route from {} to {}", self.name(source), self.name(dest)))?;
+
+        let mut start_time = self.time.now();
+        let mut legs = vec![];
+        for hop in path.windows(2) {
+            let (arrival_time, leg) =
+                self.spawn_message_entity(hop[0], hop[1], start_time, payload.clone())?;
+            if hop[1] != dest {
+                self.world.insert_one(leg, RelayedMessage).unwrap();
+            }
+            self.schedule_message(hop[0], hop[1], leg, arrival_time);
+            legs.push(leg);
+            start_time = arrival_time;
+        }
+        Ok(legs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    /// `a` can reach `c` via the short detour `b` or via the long detour `d`.
+    fn detour_network(sim: &mut Simulation) -> (Entity, Entity, Entity, Entity) {
+        let a = sim.spawn_random_node_at_position(0., 0.);
+        let b = sim.spawn_random_node_at_position(100., 10.);
+        let c = sim.spawn_random_node_at_position(200., 0.);
+        let d = sim.spawn_random_node_at_position(100., 300.);
+        sim.add_peer(a, d);
+        sim.add_peer(d, c);
+        sim.add_peer(a, b);
+        sim.add_peer(b, c);
+        (a, b, c, d)
+    }
+
+    #[wasm_bindgen_test]
+    fn shortest_path_by_latency_takes_short_detour() {
+        let mut sim = Simulation::new();
+        let (a, b, c, _) = detour_network(&mut sim);
+
+        assert_eq!(
+            Some(vec![a, b, c]),
+            sim.shortest_path(a, c, RoutingMetric::Latency)
+        );
+        assert_eq!(
+            3,
+            sim.shortest_path(a, c, RoutingMetric::Hops).unwrap().len()
+        );
+        assert_eq!(None, sim.shortest_path(c, a, RoutingMetric::Hops));
+    }
+
+    #[wasm_bindgen_test]
+    fn routed_message_legs_follow_each_other() {
+        let mut sim = Simulation::new();
+        let (a, b, c, _) = detour_network(&mut sim);
+
+        let legs = sim
+            .send_message_routed(a, c, (), RoutingMetric::Latency)
+            .unwrap();
+
+        assert_eq!(2, legs.len());
+        assert!(sim.world.get::<RelayedMessage>(legs[0]).is_ok());
+        assert!(sim.world.get::<RelayedMessage>(legs[1]).is_err());
+
+        let leg1 = *sim.world.get::<UnderlayMessage>(legs[0]).unwrap();
+        let leg2 = *sim.world.get::<UnderlayMessage>(legs[1]).unwrap();
+        assert_eq!((a, b), (leg1.source, leg1.dest));
+        assert_eq!((b, c), (leg2.source, leg2.dest));
+
+        let leg1_end = sim.world.get::<TimeSpan>(legs[0]).unwrap().end;
+        let leg2_start = sim.world.get::<TimeSpan>(legs[1]).unwrap().start;
+        assert_eq!(leg1_end, leg2_start);
+    }
+
+    #[wasm_bindgen_test]
+    fn sending_routed_message_without_route_fails() {
+        let mut sim = Simulation::new();
+        let (a, _, c, _) = detour_network(&mut sim);
+
+        assert!(sim
+            .send_message_routed(c, a, (), RoutingMetric::Hops)
+            .is_err());
+    }
+}
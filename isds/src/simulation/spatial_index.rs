@@ -0,0 +1,217 @@
+use super::*;
+use std::collections::HashMap;
+
+/// A uniform grid over node positions for answering neighbor queries without scanning all nodes.
+/// It's a snapshot: it doesn't notice nodes that move, spawn or despawn afterwards.
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(Entity, UnderlayPosition)>>,
+    len: usize,
+}
+impl SpatialIndex {
+    pub fn new(
+        nodes: impl IntoIterator<Item = (Entity, UnderlayPosition)>,
+        cell_size: f32,
+    ) -> Self {
+        let mut index = Self {
+            cell_size,
+            cells: HashMap::new(),
+            len: 0,
+        };
+        for (node, position) in nodes {
+            index
+                .cells
+                .entry(index.cell_of(position))
+                .or_default()
+                .push((node, position));
+            index.len += 1;
+        }
+        index
+    }
+    /// Indexes all nodes, choosing the cell size so that there is about one node per cell.
+    pub fn from_world(world: &World, width: f32, height: f32) -> Self {
+        let nodes: Vec<(Entity, UnderlayPosition)> = world
+            .query::<&UnderlayPosition>()
+            .with::<UnderlayNodeName>()
+            .iter()
+            .map(|(node, &position)| (node, position))
+            .collect();
+        let cell_size = (width * height / nodes.len().max(1) as f32).sqrt().max(1.);
+        Self::new(nodes, cell_size)
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// All nodes within `radius` of `position`, nearest first.
+    pub fn within_radius(&self, position: UnderlayPosition, radius: f32) -> Vec<Entity> {
+        let (min_x, min_y) = self.cell_of(UnderlayPosition::new(
+            position.x - radius,
+            position.y - radius,
+        ));
+        let (max_x, max_y) = self.cell_of(UnderlayPosition::new(
+            position.x + radius,
+            position.y + radius,
+        ));
+        let mut result = vec![];
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                result.extend(
+                    self.cell(x, y).filter(|&&(_, other)| {
+                        UnderlayPosition::distance(position, other) <= radius
+                    }),
+                );
+            }
+        }
+        Self::sorted_by_distance(position, result)
+    }
+    /// The `k` nodes closest to `position`, nearest first. Ties are broken by entity.
+    pub fn nearest_k(&self, position: UnderlayPosition, k: usize) -> Vec<Entity> {
+        let (center_x, center_y) = self.cell_of(position);
+        let mut candidates = vec![];
+        let mut ring = 0;
+        // all nodes within `ring * cell_size` are known after searching a ring
+        while candidates.len() < self.len {
+            for x in center_x - ring..=center_x + ring {
+                for y in center_y - ring..=center_y + ring {
+                    if (x - center_x).abs() == ring || (y - center_y).abs() == ring {
+                        candidates.extend(self.cell(x, y));
+                    }
+                }
+            }
+            if candidates.len() >= k {
+                let mut distances: Vec<f32> = candidates
+                    .iter()
+                    .map(|&(_, other)| UnderlayPosition::distance(position, other))
+                    .collect();
+                distances.sort_by_key(|&distance| OrderedFloat(distance));
+                if k == 0 || distances[k - 1] < ring as f32 * self.cell_size {
+                    break;
+                }
+            }
+            ring += 1;
+        }
+        let mut result = Self::sorted_by_distance(position, candidates);
+        result.truncate(k);
+        result
+    }
+    fn cell_of(&self, position: UnderlayPosition) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+    fn cell(&self, x: i32, y: i32) -> impl Iterator<Item = &(Entity, UnderlayPosition)> {
+        self.cells.get(&(x, y)).into_iter().flatten()
+    }
+    fn sorted_by_distance(
+        position: UnderlayPosition,
+        mut nodes: Vec<(Entity, UnderlayPosition)>,
+    ) -> Vec<Entity> {
+        nodes.sort_by_key(|&(node, other)| {
+            (
+                OrderedFloat(UnderlayPosition::distance(position, other)),
+                node,
+            )
+        });
+        nodes.into_iter().map(|(node, _)| node).collect()
+    }
+}
+
+impl Simulation {
+    /// The index is built lazily and rebuilt after nodes were spawned, moved or despawned.
+    pub fn spatial_index(&mut self) -> &SpatialIndex {
+        if self.spatial_index.is_none() {
+            self.spatial_index = Some(SpatialIndex::from_world(
+                &self.world,
+                self.underlay_width(),
+                self.underlay_height(),
+            ));
+        }
+        self.spatial_index.as_ref().unwrap()
+    }
+    pub(super) fn invalidate_spatial_index(&mut self) {
+        self.spatial_index = None;
+    }
+    /// The `k` nodes closest to `position`, nearest first.
+    pub fn nearest_nodes(&mut self, position: UnderlayPosition, k: usize) -> Vec<Entity> {
+        self.spatial_index().nearest_k(position, k)
+    }
+    /// All nodes within `radius` of `position`, nearest first.
+    pub fn nodes_within_radius(&mut self, position: UnderlayPosition, radius: f32) -> Vec<Entity> {
+        self.spatial_index().within_radius(position, radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    fn brute_force_sorted(
+        nodes: &[(Entity, UnderlayPosition)],
+        position: UnderlayPosition,
+    ) -> Vec<(Entity, f32)> {
+        let mut result: Vec<(Entity, f32)> = nodes
+            .iter()
+            .map(|&(node, other)| (node, UnderlayPosition::distance(position, other)))
+            .collect();
+        result.sort_by_key(|&(node, distance)| (OrderedFloat(distance), node));
+        result
+    }
+
+    #[wasm_bindgen_test]
+    fn indexed_queries_match_brute_force() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(300));
+        sim.catch_up(1.);
+        let nodes: Vec<(Entity, UnderlayPosition)> = sim
+            .nodes()
+            .into_iter()
+            .map(|(node, _, position)| (node, position))
+            .collect();
+        let index = SpatialIndex::from_world(&sim.world, 800., 800.);
+        assert_eq!(300, index.len());
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let position =
+                UnderlayPosition::new(rng.gen_range(0. ..800.), rng.gen_range(0. ..800.));
+            let expected = brute_force_sorted(&nodes, position);
+
+            for k in [0, 1, 7, 300, 500] {
+                let expected_nearest: Vec<Entity> =
+                    expected.iter().take(k).map(|&(node, _)| node).collect();
+                assert_eq!(expected_nearest, index.nearest_k(position, k));
+            }
+
+            let radius = rng.gen_range(0. ..200.);
+            let expected_within: Vec<Entity> = expected
+                .iter()
+                .filter(|&&(_, distance)| distance <= radius)
+                .map(|&(node, _)| node)
+                .collect();
+            assert_eq!(expected_within, index.within_radius(position, radius));
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn index_is_rebuilt_after_spawning() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node_at_position(10., 10.);
+        assert_eq!(
+            vec![node1],
+            sim.nearest_nodes(UnderlayPosition::new(0., 0.), 5)
+        );
+        let node2 = sim.spawn_random_node_at_position(5., 5.);
+        assert_eq!(
+            vec![node2, node1],
+            sim.nearest_nodes(UnderlayPosition::new(0., 0.), 5)
+        );
+    }
+}
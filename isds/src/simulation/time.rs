@@ -37,6 +37,10 @@ impl Time {
         debug_assert!(new_now >= self.sim_time);
         self.sim_time = new_now;
     }
+    /// Turns the clock back to zero, keeping speed and pause state.
+    pub fn reset(&mut self) {
+        self.sim_time = OrderedFloat(0.);
+    }
     pub fn set_speed(&mut self, speed: f64) {
         self.speed_factor = speed;
     }
@@ -46,11 +50,15 @@ impl Time {
     pub fn slow_down_tenfold_clamped(&mut self) {
         self.speed_factor = (self.speed_factor / 10f64).clamp(0.001f64, 1000f64);
     }
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
     pub fn toggle_paused(&mut self) {
         self.paused = !self.paused;
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TimeSpan {
     pub start: SimSeconds,
     pub end: SimSeconds,
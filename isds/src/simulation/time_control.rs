@@ -39,6 +39,9 @@ impl SlowDownOnMessages {
     }
     pub fn disable(&mut self, sim: &mut Simulation) {
         self.is_enabled = false;
+        self.forget_messages_in_flight(sim);
+    }
+    fn forget_messages_in_flight(&mut self, sim: &mut Simulation) {
         if self.messages_in_flight > 0 {
             self.messages_in_flight = 0;
             sim.time.set_speed(self.regular_speed);
@@ -79,6 +82,9 @@ impl EventHandler for SlowDownOnMessages {
         }
         Ok(())
     }
+    fn reset(&mut self, sim: &mut Simulation) {
+        self.forget_messages_in_flight(sim);
+    }
 }
 
 #[cfg(test)]
@@ -100,12 +106,12 @@ mod tests {
         let node1 = sim.spawn_random_node();
         let node2 = sim.spawn_random_node();
 
-        sim.send_message(node1, node2, ());
+        sim.send_message(node1, node2, ()).unwrap();
         sim.work_until(SimSeconds::from(0.0000001)); // not enough for message to arrive
 
         assert_eq!(slow_speed, sim.time.speed(), "didn't slow down on message");
 
-        sim.send_message(node1, node2, ());
+        sim.send_message(node1, node2, ()).unwrap();
         sim.work_until(SimSeconds::from(0.0000001)); // not enough for message to arrive
 
         assert_eq!(
@@ -140,7 +146,7 @@ mod tests {
 
         let node1 = sim.spawn_random_node();
         let node2 = sim.spawn_random_node();
-        sim.send_message(node1, node2, ());
+        sim.send_message(node1, node2, ()).unwrap();
 
         sim.catch_up(100.);
         assert_eq!(0., sim.time.speed());
@@ -157,7 +163,7 @@ mod tests {
 
         let node1 = sim.spawn_random_node();
         let node2 = sim.spawn_random_node();
-        sim.send_message(node1, node2, ());
+        sim.send_message(node1, node2, ()).unwrap();
 
         sim.catch_up(0.00001);
         assert_eq!(0.01, sim.time.speed());
@@ -172,7 +178,7 @@ mod tests {
         let node2 = sim.spawn_random_node();
 
         sim.work_until(SimSeconds::from(1.));
-        sim.send_message(node1, node2, ());
+        sim.send_message(node1, node2, ()).unwrap();
         sim.work_until(SimSeconds::from(1.)); // message is in flight
 
         sim.add_event_handler(SlowDownOnMessages::new(0.01, |_, _| true, true));
@@ -1,11 +1,32 @@
 use super::*;
+use rand::distributions::WeightedIndex;
+use rand_distr::{Distribution, Normal};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct SpawnRandomNodes(pub usize);
 impl Command for SpawnRandomNodes {
     fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
         for _ in 0..self.0 {
-            sim.spawn_random_node();
+            let node = sim.spawn_random_node();
+            sim.record_spawned_node(node)?;
+        }
+        Ok(())
+    }
+}
+
+/// Spawns `count` nodes within `spread` of `center` (but within the underlay bounds), e.g., for
+/// modeling a mining pool in a single data center.
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnCluster {
+    pub count: u32,
+    pub center: UnderlayPosition,
+    pub spread: f32,
+}
+impl Command for SpawnCluster {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        for _ in 0..self.count {
+            let node = sim.spawn_node_near(self.center, self.spread);
+            sim.record_spawned_node(node)?;
         }
         Ok(())
     }
@@ -22,6 +43,66 @@ impl Command for DespawnMostCrowdedNodes {
     }
 }
 
+/// Keeps layout routines (such as `RelaxPositions`) from moving the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinPosition(pub Entity);
+impl Command for PinPosition {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.world.insert_one(self.0, PinnedPosition)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnpinPosition(pub Entity);
+impl Command for UnpinPosition {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.world.remove_one::<PinnedPosition>(self.0).ok(); // fine if it wasn't pinned
+        Ok(())
+    }
+}
+
+/// Makes the node take the given time for processing (e.g., validating) each message it
+/// receives. Protocols see the message arrive only after that time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SetProcessingDelay(pub Entity, pub SimSeconds);
+impl Command for SetProcessingDelay {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.world.insert_one(self.0, ProcessingDelay(self.1))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetNodeColor(pub Entity, pub String);
+impl Command for SetNodeColor {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.world.insert_one(self.0, NodeColor(self.1.clone()))?;
+        Ok(())
+    }
+}
+
+/// Moves (unpinned) nodes apart that are closer to each other than the given distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelaxPositions(pub f32);
+impl Command for RelaxPositions {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.relax_positions(self.0);
+        Ok(())
+    }
+}
+
+/// Moves each (unpinned) node to a fresh random position within the underlay bounds. Peers stay
+/// the same.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShufflePositions;
+impl Command for ShufflePositions {
+    fn execute(&self, sim: &mut Simulation) -> Result<(), Box<dyn Error>> {
+        sim.shuffle_positions();
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ForRandomNode<A: EntityAction>(pub A);
 impl<A: EntityAction> Command for ForRandomNode<A> {
@@ -49,6 +130,9 @@ pub struct UnderlayConfig {
     width: f32,
     height: f32,
     message_speed: f64,
+    message_speed_factor: fn(Entity, &World) -> f64,
+    latency_jitter: f64,
+    link_capacity: Option<(usize, OverflowPolicy)>,
 }
 impl UnderlayConfig {
     pub fn new(width: f32, height: f32) -> Self {
@@ -58,14 +142,41 @@ impl UnderlayConfig {
             width,
             height,
             message_speed,
+            message_speed_factor: |_, _| 1.,
+            latency_jitter: 0.,
+            link_capacity: None,
         }
     }
 }
 
+/// What happens to messages that are sent along a link that is already at capacity, see
+/// `Simulation::set_link_capacity`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Sending fails and the message isn't spawned.
+    DropNewest,
+    /// The message departs as soon as one of the messages in flight has arrived.
+    Queue,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct UnderlayNodeName(pub String);
 
-#[derive(Debug, Copy, Clone)]
+/// Marker for nodes whose positions must not be changed by layout routines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PinnedPosition;
+
+/// How long a node takes to process a received message, see `SetProcessingDelay`. Nodes without
+/// this component process messages instantly.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessingDelay(pub SimSeconds);
+
+/// A custom fill color for drawing the node, e.g., for grouping nodes by role, see
+/// `SetNodeColor`. Nodes without this component are drawn in the default color.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeColor(pub String);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct UnderlayPosition {
     pub x: f32,
     pub y: f32,
@@ -79,6 +190,31 @@ impl UnderlayPosition {
         let y = (point1.y - point2.y).abs();
         x.hypot(y)
     }
+    pub fn midpoint(point1: Self, point2: Self) -> Self {
+        Self::lerp(point1, point2, 0.5)
+    }
+    /// The point at fraction `t` of the way from `point1` to `point2`.
+    pub fn lerp(point1: Self, point2: Self, t: f32) -> Self {
+        point1 + (point2 - point1) * t
+    }
+}
+impl std::ops::Add for UnderlayPosition {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+impl std::ops::Sub for UnderlayPosition {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+impl std::ops::Mul<f32> for UnderlayPosition {
+    type Output = Self;
+    fn mul(self, factor: f32) -> Self {
+        Self::new(self.x * factor, self.y * factor)
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -87,10 +223,15 @@ pub struct UnderlayLine {
     pub end: UnderlayPosition,
 }
 impl UnderlayLine {
-    pub fn from_nodes(world: &World, source: Entity, dest: Entity) -> Self {
-        let start = *world.get::<UnderlayPosition>(source).unwrap();
-        let end = *world.get::<UnderlayPosition>(dest).unwrap();
-        Self { start, end }
+    /// Fails if either of the nodes has no position (e.g., because it was despawned).
+    pub fn from_nodes(world: &World, source: Entity, dest: Entity) -> Result<Self, String> {
+        let start = *world
+            .get::<UnderlayPosition>(source)
+            .map_err(|_| format!("Source node ({}) has no position", source.id()))?;
+        let end = *world
+            .get::<UnderlayPosition>(dest)
+            .map_err(|_| format!("Destination node ({}) has no position", dest.id()))?;
+        Ok(Self { start, end })
     }
     pub fn length(&self) -> f32 {
         UnderlayPosition::distance(self.start, self.end)
@@ -101,6 +242,8 @@ impl UnderlayLine {
 pub struct UnderlayMessage {
     pub source: Entity,
     pub dest: Entity,
+    /// Set for requests and replies, see `Simulation::send_request`.
+    pub correlation: Option<Correlation>,
 }
 
 impl Simulation {
@@ -110,17 +253,115 @@ impl Simulation {
     pub fn underlay_height(&self) -> f32 {
         self.underlay_config.height
     }
+    /// Changes the underlay dimensions that nodes are spawned within. Like on construction, the
+    /// message speed is chosen relative to the dimensions. Nodes outside the new bounds are moved
+    /// onto the border.
+    pub fn set_bounds(&mut self, width: f32, height: f32) {
+        let UnderlayConfig {
+            message_speed_factor,
+            latency_jitter,
+            link_capacity,
+            ..
+        } = self.underlay_config;
+        self.underlay_config = UnderlayConfig {
+            message_speed_factor,
+            latency_jitter,
+            link_capacity,
+            ..UnderlayConfig::new(width, height)
+        };
+        let now = self.time.now();
+        let moved_nodes: Vec<Entity> = self
+            .world
+            .query_mut::<(&UnderlayNodeName, &mut UnderlayPosition)>()
+            .into_iter()
+            .filter_map(|(node, (_, position))| {
+                let clamped = UnderlayPosition::new(
+                    position.x.clamp(0., width),
+                    position.y.clamp(0., height),
+                );
+                (clamped != *position).then(|| {
+                    *position = clamped;
+                    node
+                })
+            })
+            .collect();
+        for node in moved_nodes {
+            self.peers_mut(node).touch(now); // for redrawing the links
+        }
+        self.invalidate_spatial_index();
+    }
+    /// Sets a function that is used for determining how fast a (freshly spawned) message travels,
+    /// relative to the default speed. Useful for letting large payloads (such as blocks) travel
+    /// slower than small ones. Factors must be positive, sending messages fails otherwise.
+    pub fn set_message_speed_factor(&mut self, message_speed_factor: fn(Entity, &World) -> f64) {
+        self.underlay_config.message_speed_factor = message_speed_factor;
+    }
+    /// Lets flight durations vary randomly, following a normal distribution around the regular
+    /// flight duration. The standard deviation is given relative to the regular flight duration,
+    /// e.g., `0.1` for 10%. Set to `0.` (the default) for deterministic flight durations.
+    pub fn set_latency_jitter(&mut self, relative_std_dev: f64) {
+        self.underlay_config.latency_jitter = relative_std_dev;
+    }
+    /// How long messages usually take along the line, i.e., without jitter and speed factors.
+    pub fn link_latency(&self, line: &UnderlayLine) -> SimSeconds {
+        OrderedFloat(f64::from(line.length()) / self.underlay_config.message_speed)
+    }
+    pub fn latency_jitter(&self) -> f64 {
+        self.underlay_config.latency_jitter
+    }
+    /// Limits how many messages can be in flight from one node to another at the same time,
+    /// modeling links with finite capacity. Set to `None` (the default) for unlimited capacity.
+    pub fn set_link_capacity(&mut self, capacity: Option<usize>, policy: OverflowPolicy) {
+        self.underlay_config.link_capacity = capacity.map(|capacity| (capacity, policy));
+    }
+    pub fn link_capacity(&self) -> Option<(usize, OverflowPolicy)> {
+        self.underlay_config.link_capacity
+    }
     pub fn spawn_random_node(&mut self) -> Entity {
-        self.world
-            .spawn(random_node(&self.underlay_config, &mut self.rng))
+        let x = self.rng.gen_range(0f32..self.underlay_config.width);
+        let y = self.rng.gen_range(0f32..self.underlay_config.height);
+        self.spawn_random_node_at_position(x, y)
     }
     pub fn spawn_random_node_at_position(&mut self, x: f32, y: f32) -> Entity {
-        self.world
-            .spawn(random_node_at_position(x, y, &mut self.rng))
+        let name = self.next_node_name();
+        self.spawn_node(name, UnderlayPosition { x, y })
+    }
+    /// Spawns a node with the given name (instead of a sequential one) at a random position.
+    pub fn spawn_named_node(&mut self, name: &str) -> Entity {
+        let x = self.rng.gen_range(0f32..self.underlay_config.width);
+        let y = self.rng.gen_range(0f32..self.underlay_config.height);
+        self.spawn_node(
+            UnderlayNodeName(name.to_string()),
+            UnderlayPosition { x, y },
+        )
+    }
+    /// Event handlers are notified only after the node has its name and position.
+    fn spawn_node(&mut self, name: UnderlayNodeName, position: UnderlayPosition) -> Entity {
+        self.invalidate_spatial_index();
+        let node = self.world.spawn((name, position));
+        self.notify_node_spawned(node);
+        node
+    }
+    /// Node names are numbered sequentially, so they are unique and the same in every run.
+    fn next_node_name(&mut self) -> UnderlayNodeName {
+        let name = UnderlayNodeName(format!("n{:04}", self.spawned_nodes));
+        self.spawned_nodes += 1;
+        name
+    }
+    /// Spawns a node at a random position within `spread` of `center`, clamped to the underlay
+    /// bounds.
+    pub fn spawn_node_near(&mut self, center: UnderlayPosition, spread: f32) -> Entity {
+        let angle = self.rng.gen_range(0f32..std::f32::consts::TAU);
+        // the square root makes positions uniformly distributed within the circle
+        let distance = spread * self.rng.gen_range(0f32..=1.).sqrt();
+        let x = (center.x + distance * angle.cos()).clamp(0., self.underlay_width());
+        let y = (center.y + distance * angle.sin()).clamp(0., self.underlay_height());
+        self.spawn_random_node_at_position(x, y)
     }
     pub fn despawn_most_crowded_node(&mut self) -> Result<(), String> {
         if let Some(node) = self.most_crowded_node() {
             self.world.despawn(node).unwrap();
+            self.invalidate_spatial_index();
             Ok(())
         } else {
             Err("No nodes left to despawn".to_string())
@@ -129,6 +370,17 @@ impl Simulation {
     pub fn pick_random_node(&mut self) -> Option<Entity> {
         self.all_nodes().choose(&mut self.rng).copied()
     }
+    /// Picks nodes with a probability proportional to their (non-negative) weight. Returns `None`
+    /// if there are no nodes or all weights are zero.
+    pub fn pick_random_node_weighted(
+        &mut self,
+        weight: impl Fn(Entity, &World) -> f64,
+    ) -> Option<Entity> {
+        let nodes = self.all_nodes();
+        let weights = nodes.iter().map(|&node| weight(node, &self.world));
+        let distribution = WeightedIndex::new(weights).ok()?;
+        Some(nodes[distribution.sample(&mut self.rng)])
+    }
     pub fn pick_random_other_node(&mut self, node: Entity) -> Option<Entity> {
         self.all_other_nodes(node).choose(&mut self.rng).copied()
     }
@@ -139,6 +391,25 @@ impl Simulation {
             .map(|(id, _)| id)
             .collect()
     }
+    /// A snapshot of all nodes with their names and positions.
+    pub fn nodes(&self) -> Vec<(Entity, String, UnderlayPosition)> {
+        self.world
+            .query::<(&UnderlayNodeName, &UnderlayPosition)>()
+            .iter()
+            .map(|(id, (name, &position))| (id, name.0.clone(), position))
+            .collect()
+    }
+    /// Resolves a node name (as shown in the UI and the log) back to the node.
+    pub fn find_node_by_name(&self, name: &str) -> Option<Entity> {
+        self.world
+            .query::<&UnderlayNodeName>()
+            .iter()
+            .find(|(_, node_name)| node_name.0 == name)
+            .map(|(node, _)| node)
+    }
+    pub fn node_count(&self) -> usize {
+        self.world.query::<&UnderlayNodeName>().iter().count()
+    }
     pub fn all_other_nodes(&mut self, node: Entity) -> Vec<Entity> {
         self.world
             .query_mut::<&UnderlayNodeName>()
@@ -147,7 +418,12 @@ impl Simulation {
             .filter(|id| *id != node)
             .collect()
     }
-    pub fn send_message<P: Payload>(&mut self, source: Entity, dest: Entity, payload: P) -> Entity {
+    pub fn send_message<P: Payload>(
+        &mut self,
+        source: Entity,
+        dest: Entity,
+        payload: P,
+    ) -> Result<Entity, String> {
         let start_time = self.time.now();
         self.spawn_and_schedule_message(source, dest, start_time, payload)
     }
@@ -156,16 +432,121 @@ impl Simulation {
         source: Entity,
         dest: Entity,
         payloads: impl IntoIterator<Item = P>,
-    ) -> Vec<Entity> {
+    ) -> Result<Vec<Entity>, String> {
         let per_message_delay = SimSeconds::from(0.001);
         let mut start_time = self.time.now();
         let mut message_entities = vec![];
         for payload in payloads.into_iter() {
-            let message_entity = self.spawn_and_schedule_message(source, dest, start_time, payload);
+            let message_entity =
+                self.spawn_and_schedule_message(source, dest, start_time, payload)?;
             message_entities.push(message_entity);
             start_time += per_message_delay;
         }
-        message_entities
+        Ok(message_entities)
+    }
+    /// Despawns all messages from `source` to `dest` that are scheduled to depart in the future.
+    /// Returns the number of dropped messages.
+    pub fn drop_queued_messages(&mut self, source: Entity, dest: Entity) -> usize {
+        let now = self.time.now();
+        let queued_messages: Vec<Entity> = self
+            .world
+            .query_mut::<(&UnderlayMessage, &TimeSpan)>()
+            .into_iter()
+            .filter(|(_, (message, time_span))| {
+                message.source == source && message.dest == dest && time_span.start > now
+            })
+            .map(|(id, _)| id)
+            .collect();
+        for &message in queued_messages.iter() {
+            self.world.despawn(message).unwrap();
+        }
+        if let Some((capacity, _)) = self.underlay_config.link_capacity {
+            // the link is only busy with the messages that already departed now
+            let mut busy_until: Vec<SimSeconds> = self
+                .world
+                .query::<(&UnderlayMessage, &TimeSpan)>()
+                .iter()
+                .filter(|(_, (message, time_span))| {
+                    message.source == source && message.dest == dest && time_span.end > now
+                })
+                .map(|(_, (_, time_span))| time_span.end)
+                .collect();
+            busy_until.sort_unstable_by(|a, b| b.cmp(a));
+            busy_until.truncate(capacity);
+            self.link_busy_until.insert((source, dest), busy_until);
+        }
+        queued_messages.len()
+    }
+    /// The in-flight messages toward `node` with their arrival times, soonest first.
+    pub fn inbound_messages(&self, node: Entity) -> Vec<(Entity, SimSeconds)> {
+        let mut inbound: Vec<(Entity, SimSeconds)> = self
+            .world
+            .query::<(&UnderlayMessage, &TimeSpan)>()
+            .iter()
+            .filter(|(_, (message, _))| message.dest == node)
+            .map(|(id, (_, time_span))| (id, time_span.end))
+            .collect();
+        inbound.sort_by_key(|&(_, arrival)| arrival);
+        inbound
+    }
+    /// A single relaxation pass: pushes each unpinned node away from all nodes that are closer
+    /// than `min_distance`, staying within the underlay bounds. Nodes at the very same position
+    /// aren't separated.
+    pub fn relax_positions(&mut self, min_distance: f32) {
+        let all_nodes: Vec<(Entity, UnderlayPosition, bool)> = self
+            .world
+            .query::<(
+                &UnderlayNodeName,
+                &UnderlayPosition,
+                Option<&PinnedPosition>,
+            )>()
+            .iter()
+            .map(|(id, (_, &position, pinned))| (id, position, pinned.is_some()))
+            .collect();
+        let now = self.time.now();
+        for &(node, position, is_pinned) in all_nodes.iter() {
+            if is_pinned {
+                continue;
+            }
+            let (mut dx, mut dy) = (0., 0.);
+            for &(other_node, other_position, _) in all_nodes.iter() {
+                let distance = UnderlayPosition::distance(position, other_position);
+                if other_node != node && distance > 0. && distance < min_distance {
+                    // each of both nodes is responsible for half of the way
+                    let push = (min_distance - distance) / 2. / distance;
+                    dx += (position.x - other_position.x) * push;
+                    dy += (position.y - other_position.y) * push;
+                }
+            }
+            if dx != 0. || dy != 0. {
+                let new_position = UnderlayPosition::new(
+                    (position.x + dx).clamp(0., self.underlay_width()),
+                    (position.y + dy).clamp(0., self.underlay_height()),
+                );
+                *self.world.get_mut::<UnderlayPosition>(node).unwrap() = new_position;
+                self.invalidate_spatial_index();
+                self.peers_mut(node).touch(now); // for redrawing the links
+            }
+        }
+    }
+    pub fn shuffle_positions(&mut self) {
+        let unpinned_nodes: Vec<Entity> = self
+            .world
+            .query::<&UnderlayNodeName>()
+            .without::<PinnedPosition>()
+            .iter()
+            .map(|(node, _)| node)
+            .collect();
+        let now = self.time.now();
+        for node in unpinned_nodes {
+            let new_position = UnderlayPosition::new(
+                self.rng.gen_range(0f32..self.underlay_config.width),
+                self.rng.gen_range(0f32..self.underlay_config.height),
+            );
+            *self.world.get_mut::<UnderlayPosition>(node).unwrap() = new_position;
+            self.peers_mut(node).touch(now); // for redrawing the links
+        }
+        self.invalidate_spatial_index();
     }
     /// Warning: Current implementation ist not very efficient!
     fn most_crowded_node(&mut self) -> Option<Entity> {
@@ -199,68 +580,131 @@ impl Simulation {
         dest: Entity,
         start_time: SimSeconds,
         payload: P,
-    ) -> Entity {
+    ) -> Result<Entity, String> {
         let (arrival_time, message_entity) =
-            self.spawn_message_entity(source, dest, start_time, payload);
+            self.spawn_message_entity(source, dest, start_time, payload)?;
         self.schedule_message(source, dest, message_entity, arrival_time);
-        message_entity
+        Ok(message_entity)
     }
-    fn spawn_message_entity<P: Payload>(
+    pub(super) fn spawn_message_entity<P: Payload>(
         &mut self,
         source: Entity,
         dest: Entity,
         start_time: SimSeconds,
         payload: P,
-    ) -> (OrderedFloat<f64>, Entity) {
-        let trajectory = UnderlayLine::from_nodes(&self.world, source, dest);
-        let flight_duration = f64::from(trajectory.length()) / self.underlay_config.message_speed;
-        let end_time = start_time + flight_duration;
+    ) -> Result<(OrderedFloat<f64>, Entity), String> {
+        let trajectory = UnderlayLine::from_nodes(&self.world, source, dest)?;
+        let start_time = self.departure_time(source, dest, start_time)?;
         let message_entity = self.world.spawn((
-            UnderlayMessage { source, dest },
-            TimeSpan {
-                start: start_time,
-                end: end_time,
+            UnderlayMessage {
+                source,
+                dest,
+                correlation: None,
             },
             trajectory,
             payload,
         ));
-        (end_time, message_entity)
+        let speed_factor = (self.underlay_config.message_speed_factor)(message_entity, &self.world);
+        if !speed_factor.is_finite() || speed_factor <= 0. {
+            self.world.despawn(message_entity).unwrap();
+            return Err(format!("Invalid message speed factor: {}", speed_factor));
+        }
+        let message_speed = self.underlay_config.message_speed * speed_factor;
+        let mut flight_duration = f64::from(trajectory.length()) / message_speed;
+        if self.underlay_config.latency_jitter > 0. {
+            let std_dev = flight_duration * self.underlay_config.latency_jitter;
+            let distribution = Normal::new(flight_duration, std_dev)
+                .map_err(|e| format!("Invalid latency jitter: {}", e))?;
+            // messages can be fast, but not infinitely fast
+            flight_duration = distribution
+                .sample(&mut self.rng)
+                .max(0.1 * flight_duration);
+        }
+        let end_time = start_time + flight_duration;
+        self.world
+            .insert_one(
+                message_entity,
+                TimeSpan {
+                    start: start_time,
+                    end: end_time,
+                },
+            )
+            .unwrap();
+        self.occupy_link(source, dest, start_time, end_time);
+        Ok((end_time, message_entity))
+    }
+    /// When a message from `source` to `dest` that is sent at `start_time` can depart, taking the
+    /// link capacity into account.
+    fn departure_time(
+        &self,
+        source: Entity,
+        dest: Entity,
+        start_time: SimSeconds,
+    ) -> Result<SimSeconds, String> {
+        let (capacity, policy) = match self.underlay_config.link_capacity {
+            Some(link_capacity) => link_capacity,
+            None => return Ok(start_time),
+        };
+        let busy_until = self.link_busy_until.get(&(source, dest));
+        let lanes_in_use = busy_until.map_or(0, Vec::len);
+        let earliest_free = busy_until.and_then(|busy_until| busy_until.iter().min().copied());
+        match earliest_free {
+            _ if lanes_in_use < capacity => Ok(start_time),
+            Some(end) if end <= start_time => Ok(start_time),
+            // with a capacity of 0, there's no in-flight message to wait for
+            Some(end) if policy == OverflowPolicy::Queue => Ok(end),
+            _ => Err(format!(
+                "Link from {:?} to {:?} is at capacity",
+                source, dest
+            )),
+        }
+    }
+    /// Marks one of the link's lanes busy until `end_time`, for a message that departs at
+    /// `start_time` as determined by `departure_time`.
+    fn occupy_link(
+        &mut self,
+        source: Entity,
+        dest: Entity,
+        start_time: SimSeconds,
+        end_time: SimSeconds,
+    ) {
+        if self.underlay_config.link_capacity.is_none() {
+            return;
+        }
+        let busy_until = self.link_busy_until.entry((source, dest)).or_default();
+        match busy_until.iter_mut().find(|end| **end <= start_time) {
+            Some(free_lane) => *free_lane = end_time,
+            None => busy_until.push(end_time),
+        }
     }
-    fn schedule_message(
+    pub(super) fn schedule_message(
         &mut self,
         source: Entity,
         dest: Entity,
         message_entity: Entity,
         arrival_time: OrderedFloat<f64>,
     ) {
-        self.schedule_now(Event::Node(source, NodeEvent::MessageSent(message_entity)));
+        // messages might depart later, e.g., after waiting for a link or a previous hop
+        let departure_time = self
+            .world
+            .get::<TimeSpan>(message_entity)
+            .map_or(self.time.now(), |time_span| time_span.start);
         self.schedule_at(
-            arrival_time,
+            departure_time,
+            Event::Node(source, NodeEvent::MessageSent(message_entity)),
+        );
+        // the message's time span still ends on arrival, processing happens afterwards
+        let processing_delay = self
+            .world
+            .get::<ProcessingDelay>(dest)
+            .map_or(OrderedFloat(0.), |delay| delay.0);
+        self.schedule_at(
+            arrival_time + processing_delay,
             Event::Node(dest, NodeEvent::MessageArrived(message_entity)),
         );
     }
 }
 
-fn random_node(
-    underlay_config: &UnderlayConfig,
-    rng: &mut impl Rng,
-) -> (UnderlayNodeName, UnderlayPosition) {
-    random_node_at_position(
-        rng.gen_range(0f32..underlay_config.width),
-        rng.gen_range(0f32..underlay_config.height),
-        rng,
-    )
-}
-
-fn random_node_at_position(
-    x: f32,
-    y: f32,
-    rng: &mut impl Rng,
-) -> (UnderlayNodeName, UnderlayPosition) {
-    let name = format!("n{:#04}", rng.gen_range(0..10_000));
-    (UnderlayNodeName(name), UnderlayPosition { x, y })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -276,12 +720,50 @@ mod tests {
         assert!(sim.world.get::<UnderlayPosition>(node_entity).is_ok());
     }
 
+    #[wasm_bindgen_test]
+    fn nodes_lists_all_nodes_with_names_and_positions() {
+        let mut sim = Simulation::new();
+        assert_eq!(0, sim.node_count());
+
+        let node1 = sim.spawn_random_node_at_position(10., 20.);
+        let node2 = sim.spawn_random_node_at_position(30., 40.);
+        sim.world.spawn(("not a node",));
+
+        let mut nodes = sim.nodes();
+        nodes.sort_by_key(|(id, _, _)| *id);
+
+        assert_eq!(2, sim.node_count());
+        assert_eq!(
+            vec![node1, node2],
+            nodes.iter().map(|n| n.0).collect::<Vec<_>>()
+        );
+        assert_eq!(sim.name(node1), nodes[0].1);
+        assert_eq!((30., 40.), (nodes[1].2.x, nodes[1].2.y));
+    }
+
+    #[wasm_bindgen_test]
+    fn nodes_can_be_found_by_name() {
+        let mut sim = Simulation::new();
+        sim.spawn_random_node();
+        let node = sim.spawn_random_node();
+        sim.world
+            .insert_one(node, UnderlayNodeName("alice".to_string()))
+            .unwrap();
+
+        assert_eq!(Some(node), sim.find_node_by_name("alice"));
+        assert_eq!(None, sim.find_node_by_name("bob"));
+        assert_eq!(
+            Some("n0000".to_string()),
+            sim.find_node_by_name("n0000").map(|node| sim.name(node))
+        );
+    }
+
     #[wasm_bindgen_test]
     fn send_message_creates_helper_fields() {
         let mut sim = Simulation::new();
         let node1 = sim.spawn_random_node();
         let node2 = sim.spawn_random_node();
-        let message_entity = sim.send_message(node1, node2, ());
+        let message_entity = sim.send_message(node1, node2, ()).unwrap();
         assert!(sim.world.get::<UnderlayLine>(message_entity).is_ok());
         assert!(sim.world.get::<TimeSpan>(message_entity).is_ok());
     }
@@ -292,7 +774,7 @@ mod tests {
         let node1 = sim.spawn_random_node();
         let node2 = sim.spawn_random_node();
         let payload = "test".to_string();
-        let message_entity = sim.send_message(node1, node2, payload.clone());
+        let message_entity = sim.send_message(node1, node2, payload.clone()).unwrap();
 
         let expected = payload;
         let actual = sim
@@ -305,6 +787,313 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[wasm_bindgen_test]
+    fn message_speed_factor_changes_flight_duration() {
+        let mut sim = Simulation::new();
+        sim.set_message_speed_factor(|message, world| {
+            if world.get::<String>(message).is_ok() {
+                0.5
+            } else {
+                1.
+            }
+        });
+        let node1 = sim.spawn_random_node_at_position(0., 0.);
+        let node2 = sim.spawn_random_node_at_position(100., 0.);
+
+        let fast_message = sim.send_message(node1, node2, ()).unwrap();
+        let slow_message = sim.send_message(node1, node2, "big".to_string()).unwrap();
+
+        let duration = |message| {
+            let time_span = sim.world.get::<TimeSpan>(message).unwrap();
+            (time_span.end - time_span.start).into_inner()
+        };
+        let expected = 2. * duration(fast_message);
+        let actual = duration(slow_message);
+        assert!((expected - actual).abs() < 1e-9);
+    }
+
+    #[wasm_bindgen_test]
+    fn latency_jitter_varies_flight_durations() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node_at_position(0., 0.);
+        let node2 = sim.spawn_random_node_at_position(100., 0.);
+        let duration = |sim: &Simulation, message| {
+            let time_span = sim.world.get::<TimeSpan>(message).unwrap();
+            (time_span.end - time_span.start).into_inner()
+        };
+
+        let messages = sim.send_messages(node1, node2, vec![(); 10]).unwrap();
+        let durations: Vec<f64> = messages.iter().map(|&m| duration(&sim, m)).collect();
+        let expected = 100. / (10. * 800.);
+        assert!(durations.iter().all(|&d| (d - expected).abs() < 1e-9));
+
+        sim.set_latency_jitter(0.2);
+        let messages = sim.send_messages(node1, node2, vec![(); 10]).unwrap();
+        let durations: Vec<f64> = messages.iter().map(|&m| duration(&sim, m)).collect();
+        assert!(durations.iter().any(|&d| (d - durations[0]).abs() > 1e-9));
+        assert!(durations.iter().all(|&d| d > 0.));
+    }
+
+    #[wasm_bindgen_test]
+    fn positions_can_be_interpolated() {
+        let point1 = UnderlayPosition::new(10., 20.);
+        let point2 = UnderlayPosition::new(30., -20.);
+        assert_eq!(
+            UnderlayPosition::new(20., 0.),
+            UnderlayPosition::midpoint(point1, point2)
+        );
+        assert_eq!(point1, UnderlayPosition::lerp(point1, point2, 0.));
+        assert_eq!(point2, UnderlayPosition::lerp(point1, point2, 1.));
+        assert_eq!(
+            UnderlayPosition::new(15., 10.),
+            UnderlayPosition::lerp(point1, point2, 0.25)
+        );
+        assert_eq!(UnderlayPosition::new(40., 0.), point1 + point2);
+        assert_eq!(UnderlayPosition::new(20., -40.), point2 - point1);
+        assert_eq!(UnderlayPosition::new(5., 10.), point1 * 0.5);
+    }
+
+    #[wasm_bindgen_test]
+    fn cluster_nodes_are_spawned_near_center() {
+        let mut sim = Simulation::new();
+        let center = UnderlayPosition::new(790., 400.);
+        sim.do_now(SpawnCluster {
+            count: 20,
+            center,
+            spread: 30.,
+        });
+        sim.catch_up(1.);
+
+        assert_eq!(20, sim.node_count());
+        for (_, _, position) in sim.nodes() {
+            assert!(UnderlayPosition::distance(center, position) <= 30. + 1e-3);
+            assert!(position.x <= sim.underlay_width());
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn relaxing_moves_only_unpinned_nodes() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node_at_position(100., 100.);
+        let node2 = sim.spawn_random_node_at_position(110., 100.);
+        let node3 = sim.spawn_random_node_at_position(500., 500.);
+
+        sim.do_now(PinPosition(node1));
+        sim.do_now(RelaxPositions(50.));
+        sim.catch_up(1.);
+
+        let position = |sim: &Simulation, node| {
+            let position = *sim.world.get::<UnderlayPosition>(node).unwrap();
+            (position.x, position.y)
+        };
+        assert_eq!((100., 100.), position(&sim, node1));
+        assert_eq!((130., 100.), position(&sim, node2));
+        assert_eq!((500., 500.), position(&sim, node3));
+
+        sim.do_now(UnpinPosition(node1));
+        sim.do_now(RelaxPositions(50.));
+        sim.catch_up(1.);
+        assert!(position(&sim, node1).0 < 100.);
+    }
+
+    #[wasm_bindgen_test]
+    fn spawned_nodes_stay_within_new_bounds() {
+        let mut sim = Simulation::new();
+        let outside = sim.spawn_random_node_at_position(700., 50.);
+        sim.set_bounds(200., 100.);
+        for _ in 0..100 {
+            sim.spawn_random_node();
+        }
+
+        assert_eq!(200., sim.underlay_width());
+        assert_eq!(100., sim.underlay_height());
+        for (_, _, position) in sim.nodes() {
+            assert!((0. ..=200.).contains(&position.x));
+            assert!((0. ..=100.).contains(&position.y));
+        }
+        assert_eq!(
+            UnderlayPosition::new(200., 50.),
+            *sim.world.get::<UnderlayPosition>(outside).unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn node_names_are_unique_and_sequential() {
+        let mut sim = Simulation::new();
+        let nodes: Vec<Entity> = (0..20_000).map(|_| sim.spawn_random_node()).collect();
+
+        let names: std::collections::BTreeSet<String> =
+            nodes.iter().map(|&node| sim.name(node)).collect();
+        assert_eq!(nodes.len(), names.len());
+        assert_eq!("n0000", sim.name(nodes[0]));
+        assert_eq!("n0042", sim.name(nodes[42]));
+        assert_eq!("n12345", sim.name(nodes[12345]));
+    }
+
+    #[wasm_bindgen_test]
+    fn shuffling_moves_nodes_but_keeps_peers() {
+        let mut sim = Simulation::new();
+        sim.do_now(SpawnRandomNodes(30));
+        sim.do_now(MakeDelaunayNetwork);
+        sim.catch_up(1.);
+        let pinned = sim.pick_random_node().unwrap();
+        sim.do_now(PinPosition(pinned));
+        sim.catch_up(1.);
+        let peers_before: Vec<(Entity, Vec<Entity>)> = sim
+            .all_nodes()
+            .into_iter()
+            .map(|node| (node, sim.peers_mut(node).iter().copied().collect()))
+            .collect();
+        let positions_before = sim.nodes();
+
+        sim.do_now(ShufflePositions);
+        sim.catch_up(1.);
+
+        let peers_after: Vec<(Entity, Vec<Entity>)> = sim
+            .all_nodes()
+            .into_iter()
+            .map(|node| (node, sim.peers_mut(node).iter().copied().collect()))
+            .collect();
+        assert_eq!(peers_before, peers_after);
+        for (node, _, position) in positions_before {
+            let new_position = *sim.world.get::<UnderlayPosition>(node).unwrap();
+            assert_eq!(node == pinned, position == new_position);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn send_message_to_despawned_node_fails() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        sim.world.despawn(node2).unwrap();
+
+        assert!(sim.send_message(node1, node2, ()).is_err());
+        assert!(sim.send_message(node2, node1, ()).is_err());
+        assert!(sim.send_messages(node1, node2, vec![(), ()]).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn messages_beyond_link_capacity_are_dropped() {
+        let mut sim = Simulation::new();
+        sim.set_link_capacity(Some(2), OverflowPolicy::DropNewest);
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        assert!(sim.send_message(node1, node2, ()).is_ok());
+        assert!(sim.send_message(node1, node2, ()).is_ok());
+        assert!(sim.send_message(node1, node2, ()).is_err());
+        // the other direction has its own capacity
+        assert!(sim.send_message(node2, node1, ()).is_ok());
+        assert_eq!(2, sim.inbound_messages(node2).len());
+
+        sim.work_until(OrderedFloat(10.));
+        assert!(sim.send_message(node1, node2, ()).is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    fn messages_beyond_link_capacity_are_queued() {
+        let mut sim = Simulation::new();
+        sim.set_link_capacity(Some(1), OverflowPolicy::Queue);
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        let messages: Vec<Entity> = (0..3)
+            .map(|_| sim.send_message(node1, node2, ()).unwrap())
+            .collect();
+
+        let time_span = |message| *sim.world.get::<TimeSpan>(message).unwrap();
+        assert_eq!(OrderedFloat(0.), time_span(messages[0]).start);
+        assert_eq!(time_span(messages[0]).end, time_span(messages[1]).start);
+        assert_eq!(time_span(messages[1]).end, time_span(messages[2]).start);
+
+        // each message is only sent once it departs
+        let expected: Vec<(Entity, SimSeconds)> = messages
+            .iter()
+            .map(|&message| (message, time_span(message).start))
+            .collect();
+        let mut sent_events = vec![];
+        while let Some((time_due, event)) = sim.event_queue.pop() {
+            if let Event::Node(_, NodeEvent::MessageSent(message)) = event {
+                sent_events.push((message, time_due));
+            }
+        }
+        assert_eq!(expected, sent_events);
+    }
+
+    #[wasm_bindgen_test]
+    fn dropped_queued_messages_free_the_link() {
+        let mut sim = Simulation::new();
+        sim.set_link_capacity(Some(1), OverflowPolicy::Queue);
+        let node1 = sim.spawn_random_node();
+        let node2 = sim.spawn_random_node();
+        let first = sim.send_message(node1, node2, ()).unwrap();
+        sim.send_message(node1, node2, ()).unwrap();
+        assert_eq!(1, sim.drop_queued_messages(node1, node2));
+
+        let first_end = sim.world.get::<TimeSpan>(first).unwrap().end;
+        let next = sim.send_message(node1, node2, ()).unwrap();
+        assert_eq!(first_end, sim.world.get::<TimeSpan>(next).unwrap().start);
+    }
+
+    #[wasm_bindgen_test]
+    fn speed_factors_change_flight_durations() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node_at_position(0., 0.);
+        let node2 = sim.spawn_random_node_at_position(100., 0.);
+        let latency =
+            sim.link_latency(&UnderlayLine::from_nodes(&sim.world, node1, node2).unwrap());
+
+        sim.set_message_speed_factor(|_, _| 0.5);
+        let message = sim.send_message(node1, node2, ()).unwrap();
+        let time_span = *sim.world.get::<TimeSpan>(message).unwrap();
+        // half the speed, twice the duration
+        assert_eq!(
+            OrderedFloat(2. * latency.into_inner()),
+            time_span.end - time_span.start
+        );
+
+        sim.set_message_speed_factor(|_, _| 0.);
+        assert!(sim.send_message(node1, node2, ()).is_err());
+        sim.set_message_speed_factor(|_, _| f64::NAN);
+        assert!(sim.send_message(node1, node2, ()).is_err());
+        assert_eq!(1, sim.world.query::<&UnderlayMessage>().iter().count());
+    }
+
+    #[wasm_bindgen_test]
+    fn inbound_messages_are_those_toward_the_node() {
+        let mut sim = Simulation::new();
+        let node1 = sim.spawn_random_node_at_position(0., 0.);
+        let node2 = sim.spawn_random_node_at_position(100., 0.);
+        let node3 = sim.spawn_random_node_at_position(0., 200.);
+        let far_message = sim.send_message(node3, node1, ()).unwrap();
+        let near_message = sim.send_message(node2, node1, ()).unwrap();
+        let _outbound_message = sim.send_message(node1, node2, ()).unwrap();
+
+        let arrival = |message| sim.world.get::<TimeSpan>(message).unwrap().end;
+        let expected = vec![
+            (near_message, arrival(near_message)),
+            (far_message, arrival(far_message)),
+        ];
+        let actual = sim.inbound_messages(node1);
+        assert_eq!(expected, actual);
+        assert!(sim.inbound_messages(node3).is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn send_message_to_entity_without_position_fails() {
+        let mut sim = Simulation::new();
+        let node = sim.spawn_random_node();
+        let bogus = sim.world.spawn(("not a node",));
+
+        assert!(sim.send_message(node, bogus, ()).is_err());
+        assert_eq!(
+            0,
+            sim.world
+                .query_mut::<&UnderlayMessage>()
+                .into_iter()
+                .count()
+        );
+    }
+
     #[wasm_bindgen_test]
     fn most_crowded_node_in_line_is_middle_node() {
         let mut sim = Simulation::new();
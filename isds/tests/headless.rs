@@ -0,0 +1,31 @@
+//! Runs the simulation natively (i.e., without a browser), e.g., via `cargo test -p isds`.
+
+use isds::nakamoto_consensus::{NakamotoConsensus, NakamotoNodeState};
+use isds::*;
+
+#[test]
+fn large_network_converges_after_many_pokes() {
+    let mut sim = Simulation::new();
+    sim.add_event_handler(InvokeProtocolForAllNodes(NakamotoConsensus::default()));
+    sim.do_now(SpawnRandomNodes(100));
+    sim.do_now(MakeDelaunayNetwork);
+    sim.work_until(OrderedFloat(1.));
+
+    for i in 0..1000 {
+        sim.do_in(OrderedFloat(i as f64), ForRandomNode(PokeNode));
+    }
+    sim.work_until(OrderedFloat(1000.));
+
+    assert!(sim.work_until_converged(OrderedFloat(100.)).is_some());
+    let node = sim.pick_random_node().unwrap();
+    let tip_height = sim
+        .world
+        .get::<NakamotoNodeState>(node)
+        .unwrap()
+        .tip_height();
+    assert!(
+        tip_height > 900,
+        "too many forks: tip height is {}",
+        tip_height
+    );
+}
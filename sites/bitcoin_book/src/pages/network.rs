@@ -70,13 +70,6 @@ impl Component for Standalone {
     }
 
     fn view(&self, _: &Context<Self>) -> Html {
-        let on_node_click = {
-            let sim = self.sim.clone();
-            Callback::from(move |node| {
-                sim.borrow_mut()
-                    .do_now(isds::ForSpecific(node, isds::nakamoto_consensus::MineBlock))
-            })
-        };
         html! {
             <isds::Isds sim={ self.sim.clone() }>
                 <isds::TimeUi
@@ -85,7 +78,6 @@ impl Component for Standalone {
                     }
                 />
                 <isds::NetView
-                    { on_node_click }
                     buffer_space=25. // just enough for nodes and blockchains to be fully visible
                 />
             </isds::Isds>